@@ -1,7 +1,7 @@
 use rstrace::bvh::BvhNode;
 use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
 use rstrace::geometry::{Axis, Quad, Rotate, Translate};
-use rstrace::material::{Emitter, Lambertian};
+use rstrace::material::{Emitter, EmitterOptions, Lambertian};
 use rstrace::ray::Hittables;
 use rstrace::texture::SolidTex;
 use rstrace::vec::*;
@@ -46,11 +46,15 @@ fn main() {
         y: 0.73,
         z: 0.73,
     }));
-    let light = Emitter::new(SolidTex::new(Color {
-        x: 15.0,
-        y: 15.0,
-        z: 15.0,
-    }));
+    // the ceiling panel only shines down into the box
+    let light = Emitter::with_options(
+        SolidTex::white(),
+        EmitterOptions {
+            intensity: 15.0,
+            one_sided: true,
+            ..Default::default()
+        },
+    );
 
     let right_quad = Quad::new_arc(
         Point {
@@ -97,14 +101,14 @@ fn main() {
             z: 332.0,
         },
         Vec3 {
-            x: -130.0,
+            x: 0.0,
             y: 0.0,
-            z: 0.0,
+            z: -105.0,
         },
         Vec3 {
-            x: 0.0,
+            x: -130.0,
             y: 0.0,
-            z: -105.0,
+            z: 0.0,
         },
         light,
     );
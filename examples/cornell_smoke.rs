@@ -1,7 +1,8 @@
 use rstrace::bvh::BvhNode;
 use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
 use rstrace::geometry::{Axis, ConstantMedium, Quad, Rotate, Translate};
-use rstrace::material::{Emitter, Isotropic, Lambertian};
+use rstrace::material::{Anisotropic, Emitter, EmitterOptions, Isotropic, Lambertian};
+use rstrace::phase::Phase;
 use rstrace::ray::Hittables;
 use rstrace::texture::SolidTex;
 use rstrace::vec::*;
@@ -46,11 +47,15 @@ fn main() {
         y: 0.73,
         z: 0.73,
     }));
-    let light = Emitter::new(SolidTex::new(Color {
-        x: 7.0,
-        y: 7.0,
-        z: 7.0,
-    }));
+    // the ceiling panel only shines down into the box
+    let light = Emitter::with_options(
+        SolidTex::white(),
+        EmitterOptions {
+            intensity: 7.0,
+            one_sided: true,
+            ..Default::default()
+        },
+    );
 
     let right_quad = Quad::new_arc(
         Point {
@@ -97,14 +102,14 @@ fn main() {
             z: 127.0,
         },
         Vec3 {
-            x: 330.0,
+            x: 0.0,
             y: 0.0,
-            z: 0.0,
+            z: 305.0,
         },
         Vec3 {
-            x: 0.0,
+            x: 330.0,
             y: 0.0,
-            z: 305.0,
+            z: 0.0,
         },
         light,
     );
@@ -205,7 +210,11 @@ fn main() {
             },
         ),
         0.01,
-        Isotropic::new(SolidTex::new(Color::splat(1.0))),
+        // smoke particles scatter most of the light forward
+        Anisotropic::new(
+            SolidTex::new(Color::splat(1.0)),
+            Phase::HenyeyGreenstein(0.6),
+        ),
     );
 
     let mut world = Hittables::from_vec(vec![
@@ -0,0 +1,174 @@
+use std::env;
+use std::process::Command;
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+use rstrace::bvh::BvhNode;
+use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
+use rstrace::distributed::{self, Coordinator, Job};
+use rstrace::geometry::Sphere;
+use rstrace::material::{Dielectric, Lambertian, Metal};
+use rstrace::ray::{Hittable, Hittables};
+use rstrace::texture::SolidTex;
+use rstrace::vec::*;
+
+// Farms a single frame out to worker processes over TCP:
+//
+// $ cargo run --release --example distributed -- coordinator 127.0.0.1:7878
+// $ cargo run --release --example distributed -- worker 127.0.0.1:7878   (as many as you like)
+//
+// or let the example spawn the workers on localhost itself:
+//
+// $ cargo run --release --example distributed -- local 4
+const SAMPLES: u32 = 100;
+const BATCHES: u32 = 4;
+
+fn camera() -> Camera<SmallRng> {
+    let mut intrinsics = CameraIntrinsics::default();
+    intrinsics.img_w = 800;
+    intrinsics.vfov = 30.0;
+    intrinsics.max_bounces = 20;
+
+    let pose = CameraPose {
+        lookfrom: Point {
+            x: 0.0,
+            y: 1.5,
+            z: 6.0,
+        },
+        lookat: Point {
+            x: 0.0,
+            y: 0.5,
+            z: 0.0,
+        },
+        vup: Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+    };
+
+    Camera::new_seeded_rng(intrinsics, pose, 316)
+}
+
+// workers have to agree on the scene, so it's built without any randomness
+fn world(rng: &mut SmallRng) -> Arc<dyn Hittable<SmallRng>> {
+    let mut world = Hittables::new();
+    world.add(Sphere::new_arc(
+        1000.0,
+        Point {
+            x: 0.0,
+            y: -1000.0,
+            z: 0.0,
+        },
+        Lambertian::new(SolidTex::new(Color::splat(0.5))),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: -1.2,
+            y: 0.5,
+            z: 0.0,
+        },
+        Lambertian::new(SolidTex::new(Color {
+            x: 0.7,
+            y: 0.2,
+            z: 0.1,
+        })),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: 0.0,
+            y: 0.5,
+            z: 0.0,
+        },
+        Dielectric::new(1.5),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: 1.2,
+            y: 0.5,
+            z: 0.0,
+        },
+        Metal::new(SolidTex::new(Color::splat(0.8)), 0.05),
+    ));
+
+    BvhNode::from_hittables(&mut world.objects, rng)
+}
+
+fn coordinator(addr: &str) -> Coordinator {
+    let (img_w, img_h) = camera().img_dims();
+    // split the frame into tiles and every tile into sample batches
+    let mut jobs = Job::tiles(img_w, img_h, 4, 4, SAMPLES / BATCHES);
+    let tiles = jobs.len() as u32;
+    for batch in 1..BATCHES {
+        jobs.extend(
+            Job::tiles(img_w, img_h, 4, 4, SAMPLES / BATCHES)
+                .into_iter()
+                .map(|job| Job {
+                    id: job.id + batch * tiles,
+                    seed: (job.id + batch * tiles) as u64,
+                    ..job
+                }),
+        );
+    }
+    Coordinator::bind(addr, img_w, img_h, jobs).expect("failed to bind coordinator")
+}
+
+fn worker(addr: &str) {
+    let mut camera = camera();
+    let mut rng = camera.get_rng();
+    let world = world(&mut rng);
+
+    distributed::work(addr, |job| {
+        camera.set_region(Some(job.region));
+        camera.set_rays_per_pixel(job.samples);
+        camera.set_seed(job.seed);
+        camera.render_tile(world.clone())
+    })
+    .expect("worker failed");
+}
+
+fn usage() -> ! {
+    eprintln!("usage: distributed coordinator <addr> | worker <addr> | local <workers>");
+    std::process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_else(|| usage());
+
+    let frame = match arg(0) {
+        "coordinator" => coordinator(arg(1)).run(),
+        "worker" => return worker(arg(1)),
+        "local" => {
+            let workers: u32 = arg(1).parse().unwrap_or_else(|_| usage());
+            let coordinator = coordinator("127.0.0.1:0");
+            let addr = coordinator.local_addr().expect("no local address");
+
+            let exe = env::current_exe().expect("no current executable");
+            let mut children: Vec<_> = (0..workers)
+                .map(|_| {
+                    Command::new(&exe)
+                        .args(["worker", &addr.to_string()])
+                        .spawn()
+                        .expect("failed to spawn worker")
+                })
+                .collect();
+
+            let frame = coordinator.run();
+            for child in children.iter_mut() {
+                let _ = child.wait();
+            }
+            frame
+        }
+        _ => usage(),
+    };
+
+    frame
+        .expect("coordinator failed")
+        .save("distributed.png")
+        .expect("failed to save image");
+    println!("Wrote distributed.png");
+}
@@ -1,8 +1,9 @@
-use rand::rngs::ThreadRng;
 use rstrace::bvh::BvhNode;
 use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
-use rstrace::geometry::{ConstantMedium, Quad, Sphere};
-use rstrace::material::{Emitter, Isotropic, Lambertian, Metal};
+use rstrace::geometry::{Quad, Sphere};
+use rstrace::material::{Emitter, Metal, OrenNayar};
+use rstrace::medium::Atmosphere;
+use rstrace::phase::Phase;
 use rstrace::ray::Hittables;
 use rstrace::texture::{ImageTex, SolidTex};
 use rstrace::vec::*;
@@ -14,6 +15,12 @@ fn main() {
     intrinsics.rays_per_pixel = 100;
     intrinsics.background = Color::zero();
     intrinsics.img_w = 1000;
+    // thin haze around the whole scene. Forward scattering gives it a glow around the light.
+    intrinsics.atmosphere = Some(Atmosphere {
+        density: 0.1,
+        phase: Phase::HenyeyGreenstein(0.7),
+        ..Default::default()
+    });
 
     let pose = CameraPose::default();
     let camera = Camera::new_default_rng(intrinsics, pose);
@@ -23,13 +30,13 @@ fn main() {
     let earth_tex = ImageTex::new("assets/textures/earth.jpg").unwrap();
     let mars_tex = ImageTex::new("assets/textures/mars.jpg").unwrap();
     let moon_tex = ImageTex::new("assets/textures/moon.jpg").unwrap();
-    let fog_tex = SolidTex::new(Color::white());
 
     // --- Materials ---
     let earth_mat = Metal::new(earth_tex, 1.0);
     let mars_mat = Metal::new(mars_tex, 1.0);
-    let moon_mat = Metal::new(moon_tex, 1.0);
-    let fog_mat = Isotropic::new(fog_tex);
+    // the lunar regolith is the textbook example of a rough diffuse surface: a full moon looks
+    // almost equally bright at its rim and its center
+    let moon_mat = OrenNayar::new(moon_tex, 30.0);
 
     let light_mat = Emitter::new(SolidTex::new(Color {
         x: 7.0,
@@ -97,19 +104,8 @@ fn main() {
         Metal::new(SolidTex::new((88, 91, 112).into()), 1.0),
     );
 
-    let fog_bound = Sphere::new_arc(
-        100.0,
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        },
-        Lambertian::new(SolidTex::white()),
-    );
-
-    let fog = ConstantMedium::<ThreadRng>::new_arc(fog_bound, 0.1, fog_mat);
     // --- World ---
-    let mut world = Hittables::from_vec(vec![earth, mars, moon, light, world_floor, fog]);
+    let mut world = Hittables::from_vec(vec![earth, mars, moon, light, world_floor]);
     let world_root = BvhNode::from_hittables(&mut world.objects, &mut rng);
 
     // --- Render ---
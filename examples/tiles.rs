@@ -0,0 +1,154 @@
+use std::env;
+use std::sync::Arc;
+
+use rand::rngs::ThreadRng;
+use rstrace::bvh::BvhNode;
+use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
+use rstrace::geometry::Sphere;
+use rstrace::material::{Dielectric, Lambertian, Metal};
+use rstrace::ray::{Hittable, Hittables};
+use rstrace::texture::{CheckerTex, SolidTex};
+use rstrace::tile::{self, Region};
+use rstrace::vec::*;
+
+// Splits a poster render into tiles that can be rendered by independent processes:
+//
+// $ cargo run --release --example tiles -- render 0 4 &
+// $ cargo run --release --example tiles -- render 1 4 &
+// $ cargo run --release --example tiles -- render 2 4 &
+// $ cargo run --release --example tiles -- render 3 4 &
+// $ cargo run --release --example tiles -- merge tiles@*.png
+//
+// A noisy area can be re-rendered at a higher sample count and pasted over the merged image:
+//
+// $ cargo run --release --example tiles -- patch 600 200 300 200 2000
+const OUT: &str = "tiles.png";
+
+fn camera(rays_per_pixel: u32, region: Option<Region>) -> Camera<ThreadRng> {
+    let mut intrinsics = CameraIntrinsics::default();
+    intrinsics.img_w = 1600;
+    intrinsics.vfov = 30.0;
+    intrinsics.rays_per_pixel = rays_per_pixel;
+    intrinsics.max_bounces = 20;
+    intrinsics.region = region;
+
+    let pose = CameraPose {
+        lookfrom: Point {
+            x: 0.0,
+            y: 1.5,
+            z: 6.0,
+        },
+        lookat: Point {
+            x: 0.0,
+            y: 0.5,
+            z: 0.0,
+        },
+        vup: Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+    };
+
+    Camera::new_default_rng(intrinsics, pose)
+}
+
+// every process has to build the exact same scene, so we don't use any randomness here
+fn world(rng: &mut ThreadRng) -> Arc<dyn Hittable<ThreadRng>> {
+    let mut world = Hittables::new();
+    world.add(Sphere::new_arc(
+        1000.0,
+        Point {
+            x: 0.0,
+            y: -1000.0,
+            z: 0.0,
+        },
+        Lambertian::new(CheckerTex::new(Color::splat(0.2), Color::splat(0.8), 400.0)),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: -1.2,
+            y: 0.5,
+            z: 0.0,
+        },
+        Lambertian::new(SolidTex::new(Color {
+            x: 0.7,
+            y: 0.2,
+            z: 0.1,
+        })),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: 0.0,
+            y: 0.5,
+            z: 0.0,
+        },
+        Dielectric::new(1.5),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: 1.2,
+            y: 0.5,
+            z: 0.0,
+        },
+        Metal::new(SolidTex::new(Color::splat(0.8)), 0.05),
+    ));
+
+    BvhNode::from_hittables(&mut world.objects, rng)
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: tiles render <index> <count> | merge <tile>... | patch <x> <y> <w> <h> <spp>"
+    );
+    std::process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let num = |i: usize| -> u32 {
+        args.get(i)
+            .and_then(|arg| arg.parse().ok())
+            .unwrap_or_else(|| usage())
+    };
+
+    match args.first().map(String::as_str) {
+        Some("render") => {
+            let (index, count) = (num(1), num(2));
+            let mut camera = camera(100, None);
+            let (img_w, img_h) = camera.img_dims();
+            let regions = Region::grid(img_w, img_h, 1, count);
+            camera.set_region(Some(
+                *regions.get(index as usize).unwrap_or_else(|| usage()),
+            ));
+
+            let mut rng = camera.get_rng();
+            let path = camera
+                .render_tile(world(&mut rng))
+                .save_as_tile(OUT, img_w, img_h)
+                .expect("failed to save tile");
+            println!("Wrote {}", path.display());
+        }
+        Some("merge") => {
+            tile::merge(&args[1..], OUT).expect("failed to merge tiles");
+            println!("Wrote {OUT}");
+        }
+        Some("patch") => {
+            let region = Region::new(num(1), num(2), num(3), num(4));
+            let camera = camera(num(5), Some(region));
+            let (img_w, img_h) = camera.img_dims();
+
+            let mut rng = camera.get_rng();
+            let patch = camera
+                .render_tile(world(&mut rng))
+                .save_as_tile("patch.png", img_w, img_h)
+                .expect("failed to save patch");
+            tile::composite(OUT, &[patch], OUT).expect("failed to composite patch");
+            println!("Patched {OUT}");
+        }
+        _ => usage(),
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     aabb::AABB,
     interval::Interval,
     ray::{Hit, Hittable, Ray3},
+    vec::Color,
 };
 
 // represents both individual nodes in the tree as well as the tree itself (root node)
@@ -16,6 +17,8 @@ pub struct BvhNode<R: Rng> {
     left: Arc<dyn Hittable<R>>,
     right: Arc<dyn Hittable<R>>,
     bbox: AABB,
+    // whether any object below the node has a pass weight, see `Hittable::pass_weight`
+    has_pass_weight: bool,
 }
 
 impl<R: Rng> std::fmt::Debug for BvhNode<R> {
@@ -24,6 +27,7 @@ impl<R: Rng> std::fmt::Debug for BvhNode<R> {
             .field("bbox", &self.bbox)
             .field("left", &self.left)
             .field("right", &self.right)
+            .field("has_pass_weight", &self.has_pass_weight)
             .finish()
     }
 }
@@ -50,6 +54,22 @@ impl<R: Rng> Hittable<R> for BvhNode<R> {
     fn bbox(&self) -> AABB {
         self.bbox
     }
+
+    fn pass_weight(&self, ray: &Ray3, t_range: &Interval, rng: &mut R) -> Color {
+        if !self.has_pass_weight || !self.bbox().hit(ray, *t_range) {
+            return Color::white();
+        }
+        let left = self.left.pass_weight(ray, t_range, rng);
+        // leaves with a single object hold it on both sides
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        &left * &self.right.pass_weight(ray, t_range, rng)
+    }
+
+    fn has_pass_weight(&self) -> bool {
+        self.has_pass_weight
+    }
 }
 
 impl<R: Rng + 'static> BvhNode<R> {
@@ -95,6 +115,7 @@ impl<R: Rng + 'static> BvhNode<R> {
 
         Arc::from(Self {
             bbox: AABB::from_bboxes(&left.bbox(), &right.bbox()),
+            has_pass_weight: left.has_pass_weight() || right.has_pass_weight(),
             left,
             right,
         })
@@ -1,11 +1,14 @@
 use crate::{
+    environment::EnvironmentMap,
     interval::Interval,
-    ray::{Hittable, Ray3},
-    utils::{linear_to_gamma, map_rgb},
+    medium::{Atmosphere, FreeFlight},
+    ray::{Hit, Hittable, Ray3},
+    spectrum::{self, ColorSpace},
+    tile::{Region, Tile},
     vec::{Color, Pixel, Point, Vec3},
 };
 use core::f64;
-use image::{ImageBuffer, ImageResult, Rgb};
+use image::ImageResult;
 use rand::{
     rngs::{SmallRng, ThreadRng},
     Rng, SeedableRng,
@@ -45,6 +48,16 @@ pub struct CameraIntrinsics {
     pub defoucs_angle: f64,
     pub focus_distance: f64,
     pub background: Color,
+    // only render this sub-rectangle of the image (the projection stays the same)
+    pub region: Option<Region>,
+    // trace a single wavelength per camera ray and accumulate CIE XYZ instead of tracing rgb
+    pub spectral: bool,
+    // colour space of the rendered pixels
+    pub color_space: ColorSpace,
+    // medium filling the space around all objects that every ray outside of them travels through
+    pub atmosphere: Option<Atmosphere>,
+    // image based light that replaces the background colour for rays that miss all objects
+    pub environment: Option<EnvironmentMap>,
 }
 
 impl Default for CameraIntrinsics {
@@ -58,16 +71,33 @@ impl Default for CameraIntrinsics {
             defoucs_angle: 0.0,
             focus_distance: 1.0,
             background: (108, 166, 193).into(),
+            region: None,
+            spectral: false,
+            color_space: ColorSpace::Srgb,
+            atmosphere: None,
+            environment: None,
         }
     }
 }
 
 pub type RenderResult<T> = ImageResult<T>;
 
+// how the direction of a ray was chosen, which decides how much of the environment it sees when it
+// misses all objects
+#[derive(Clone, Copy)]
+enum RayKind {
+    Camera,
+    // scattered by a material or medium that didn't sample the environment directly
+    Scatter,
+    // sampled by a material with the given pdf after it sampled the environment directly as well
+    Bsdf { pdf: f64 },
+}
+
 // make Camera generic over R so we can potentially use different rngs later
 pub struct Camera<R: Rng> {
     img_w: u32,
     img_h: u32,
+    region: Region,
     px00: Point,
     px_delta_u: Vec3,
     px_delta_v: Vec3,
@@ -78,6 +108,10 @@ pub struct Camera<R: Rng> {
     max_bounces: u32,
     pose: CameraPose,
     background: Color,
+    spectral: bool,
+    color_space: ColorSpace,
+    atmosphere: Option<Atmosphere>,
+    environment: Option<EnvironmentMap>,
     // use function pointer for PhantomData<T> so we get the Sync + Send auto trait implementations
     rng_marker: PhantomData<fn() -> R>,
     rng_base_seed: Option<u64>,
@@ -129,9 +163,15 @@ impl<R: Rng> Camera<R> {
         let defocus_disk_u = u * defocus_disk_radius;
         let defocus_disk_v = v * defocus_disk_radius;
 
+        let region = intrinsics
+            .region
+            .unwrap_or(Region::full(intrinsics.img_w, img_h))
+            .clip(intrinsics.img_w, img_h);
+
         Camera {
             img_w: intrinsics.img_w,
             img_h,
+            region,
             px00,
             px_delta_u,
             px_delta_v,
@@ -142,27 +182,48 @@ impl<R: Rng> Camera<R> {
             max_bounces: intrinsics.max_bounces,
             pose,
             background: intrinsics.background,
+            spectral: intrinsics.spectral,
+            color_space: intrinsics.color_space,
+            atmosphere: intrinsics.atmosphere,
+            environment: intrinsics.environment,
             rng_marker: PhantomData,
             rng_base_seed: seed,
         }
     }
 
-    fn render_with<F>(
-        &self,
-        world: Arc<dyn Hittable<R>>,
-        path: impl AsRef<Path>,
-        make_rng: F,
-    ) -> RenderResult<()>
+    pub fn img_dims(&self) -> (u32, u32) {
+        (self.img_w, self.img_h)
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: Option<Region>) {
+        self.region = region
+            .unwrap_or(Region::full(self.img_w, self.img_h))
+            .clip(self.img_w, self.img_h);
+    }
+
+    pub fn set_rays_per_pixel(&mut self, rays_per_pixel: u32) {
+        self.rays_per_pixel = rays_per_pixel;
+    }
+
+    fn render_tile_with<F>(&self, world: Arc<dyn Hittable<R>>, make_rng: F) -> Tile
     where
         F: Fn(u64) -> R + Send + Clone + Copy + 'static,
     {
         let start = Instant::now();
-        println!("Rendering image @ {}x{}...", self.img_w, self.img_h);
+        let region = self.region;
+        println!(
+            "Rendering region {}x{} @ ({}, {}) of image @ {}x{}...",
+            region.w, region.h, region.x, region.y, self.img_w, self.img_h
+        );
 
         let num_cpus = num_cpus::get();
         println!("{num_cpus} thread(s) available!");
 
-        let rows_per_thread = (self.img_h as f64 / num_cpus as f64).ceil() as usize;
+        let rows_per_thread = (region.h as f64 / num_cpus as f64).ceil() as usize;
         println!("Processing {rows_per_thread} rows per thread!");
 
         let mut pixels = thread::scope(|scope| {
@@ -176,30 +237,53 @@ impl<R: Rng> Camera<R> {
                                 Vec::with_capacity(rows_per_thread);
                             let mut rng = make_rng(i as u64);
                             let start = i * rows_per_thread;
-                            let end = (self.img_h as usize).min(start + rows_per_thread);
+                            let end = (region.h as usize).min(start + rows_per_thread);
                             for y in start..end {
-                                let mut row: Vec<Pixel> = Vec::with_capacity(self.img_w as usize);
-                                for x in 0..self.img_w {
+                                let mut row: Vec<Pixel> = Vec::with_capacity(region.w as usize);
+                                for x in 0..region.w {
                                     let mut px = Pixel::zero();
 
                                     for _ in 0..self.rays_per_pixel {
-                                        let ray = self.get_ray(x, y as u32, &mut rng);
-                                        px = px
-                                            + self.color_ray(
+                                        let mut ray = self.get_ray(
+                                            region.x + x,
+                                            region.y + y as u32,
+                                            &mut rng,
+                                        );
+                                        if !self.spectral {
+                                            px = px
+                                                + self.color_ray(
+                                                    &ray,
+                                                    world.clone(),
+                                                    self.max_bounces,
+                                                    RayKind::Camera,
+                                                    &mut rng,
+                                                );
+                                            continue;
+                                        }
+
+                                        // every path carries the radiance of a single
+                                        // wavelength, which all channels of the returned
+                                        // "colour" hold
+                                        let lambda = spectrum::sample_wavelength(&mut rng);
+                                        ray.wavelength = Some(lambda);
+                                        let radiance = self
+                                            .color_ray(
                                                 &ray,
                                                 world.clone(),
                                                 self.max_bounces,
+                                                RayKind::Camera,
                                                 &mut rng,
-                                            );
+                                            )
+                                            .x;
+                                        px = px + spectrum::wavelength_to_xyz(lambda, radiance);
                                     }
 
-                                    px = px / self.rays_per_pixel as f64;
-
-                                    px.x = map_rgb(linear_to_gamma(px.x));
-                                    px.y = map_rgb(linear_to_gamma(px.y));
-                                    px.z = map_rgb(linear_to_gamma(px.z));
-
-                                    row.push(px);
+                                    let px = px / self.rays_per_pixel as f64;
+                                    row.push(if self.spectral {
+                                        self.color_space.from_xyz(&px)
+                                    } else {
+                                        self.color_space.from_srgb(&px)
+                                    });
                                 }
                                 rows.push((y, row));
                             }
@@ -221,19 +305,9 @@ impl<R: Rng> Camera<R> {
         let end = start.elapsed().as_secs_f64();
         println!("Computed rays in {:.2} seconds", end);
 
-        let mut image = ImageBuffer::new(self.img_w, self.img_h);
-
-        for (y, row) in pixels.into_iter() {
-            for (x, px) in row.into_iter().enumerate() {
-                image.put_pixel(
-                    x as u32,
-                    y as u32,
-                    Rgb::from([px.x as u8, px.y as u8, px.z as u8]),
-                );
-            }
-        }
-
-        image.save(path)
+        let mut tile = Tile::new(region, self.rays_per_pixel);
+        tile.pixels = pixels.into_iter().flat_map(|(_, row)| row).collect();
+        tile
     }
 
     fn color_ray(
@@ -241,6 +315,7 @@ impl<R: Rng> Camera<R> {
         ray: &Ray3,
         world: Arc<dyn Hittable<R>>,
         bounces_left: u32,
+        kind: RayKind,
         rng: &mut R,
     ) -> Pixel {
         if bounces_left <= 0 {
@@ -252,31 +327,207 @@ impl<R: Rng> Camera<R> {
             max: f64::INFINITY,
         };
 
-        if let Some(hit) = world.hit(&ray, &mut t_range, rng) {
-            // if we hit an emissive material we won't scatter and we will directly return the
-            // emissive color up the stack
-            let emission_color = hit.mat.emit(hit.uv, &hit.p);
+        let hit = world.hit(&ray, &mut t_range, rng);
+
+        // rays inside of a scattering interior (wax, marble, skin) might scatter before they reach
+        // the next surface. The resulting random walk goes on until the ray refracts out again.
+        let mut medium_weight = Color::white();
+        if let Some(interior) = ray.media.top().filter(|interior| interior.is_scattering()) {
+            let max_distance = hit
+                .as_ref()
+                .map_or(f64::INFINITY, |hit| hit.t * ray.dir.len());
+            match interior.free_flight(max_distance, rng) {
+                FreeFlight::Scatter { distance, weight } => {
+                    let passed = self.pass_weight(ray, &world, distance / ray.dir.len(), rng);
+                    let scattered_ray = ray.spawn(
+                        ray.at(distance / ray.dir.len()),
+                        interior.phase.sample(&ray.dir, rng),
+                    );
+                    return &(&self.color_ray(
+                        &scattered_ray,
+                        world,
+                        bounces_left - 1,
+                        RayKind::Scatter,
+                        rng,
+                    ) * &self.reflectance(ray, weight))
+                        * &passed;
+                }
+                FreeFlight::Pass { weight } => medium_weight = self.reflectance(ray, weight),
+            }
+        }
 
-            if let Some(scatter) = hit.mat.scatter(ray, &hit, rng) {
-                return &self.color_ray(
-                    &Ray3::with_time(hit.p, scatter.scattered_ray.dir, scatter.scattered_ray.time),
+        // rays outside of all objects travel through the atmosphere, including the ones that
+        // escape to the background
+        if let Some(atmosphere) = self.atmosphere.filter(|_| ray.media.is_empty()) {
+            let ray_len = ray.dir.len();
+            let max_distance = hit.as_ref().map_or(f64::INFINITY, |hit| hit.t * ray_len);
+            let flight = atmosphere.free_flight(&ray.origin, &ray.dir.norm(), max_distance, rng);
+            if let FreeFlight::Scatter { distance, weight } = flight {
+                let passed = self.pass_weight(ray, &world, distance / ray_len, rng);
+                let scattered_ray = ray.spawn(
+                    ray.at(distance / ray_len),
+                    atmosphere.phase.sample(&ray.dir, rng),
+                );
+                return &(&self.color_ray(
+                    &scattered_ray,
                     world,
                     bounces_left - 1,
+                    RayKind::Scatter,
                     rng,
-                ) * &scatter.attenuation;
+                ) * &self.reflectance(ray, weight))
+                    * &passed;
+            }
+        }
+
+        if let Some(hit) = hit {
+            // light travelling along the ray is absorbed by the interior the ray is currently in
+            // (e.g. coloured glass) according to the distance between the ray origin and the hit
+            let transmittance = match ray.media.top() {
+                Some(interior) if interior.is_scattering() => medium_weight,
+                Some(interior) if interior.is_absorbing() => {
+                    self.reflectance(ray, interior.transmittance(hit.t * ray.dir.len()))
+                }
+                _ => Color::white(),
+            };
+            let transmittance = &transmittance * &self.pass_weight(ray, &world, hit.t, rng);
+
+            // if we hit an emissive material we won't scatter and we will directly return the
+            // emissive color up the stack
+            let emission_color = self.emission(ray, &hit);
+
+            if let Some(mut scatter) = hit.mat.scatter(ray, &hit, rng) {
+                // the cone of the ray keeps growing after the bounce, so textures seen in mirrors
+                // are filtered too
+                scatter.scattered_ray.cone = ray.cone.advance(hit.t * ray.dir.len());
+
+                // materials that can be evaluated sample the environment directly as well
+                let mut direct = Pixel::zero();
+                let mut kind = RayKind::Scatter;
+                if let Some(env) = &self.environment {
+                    if let Some(eval) = hit.mat.eval(ray, &hit, &scatter.scattered_ray.dir) {
+                        direct = self.sample_environment(env, ray, &hit, &world, rng);
+                        if eval.pdf > 0.0 {
+                            kind = RayKind::Bsdf { pdf: eval.pdf };
+                        }
+                    }
+                }
+
+                let indirect =
+                    &self.color_ray(&scatter.scattered_ray, world, bounces_left - 1, kind, rng)
+                        * &self.reflectance(ray, scatter.attenuation);
+                return &(indirect + direct) * &transmittance;
             } else {
-                return Pixel {
-                    x: emission_color.x,
-                    y: emission_color.y,
-                    z: emission_color.z,
-                };
+                return &emission_color * &transmittance;
             }
         } else {
-            return Pixel {
-                x: self.background.x,
-                y: self.background.y,
-                z: self.background.z,
-            };
+            let passed = self.pass_weight(ray, &world, f64::INFINITY, rng);
+            return &self.escaped(ray, kind) * &passed;
+        }
+    }
+
+    // radiance arriving along a ray that missed all objects
+    fn escaped(&self, ray: &Ray3, kind: RayKind) -> Pixel {
+        let env = self
+            .environment
+            .as_ref()
+            .filter(|env| env.visible_to_camera() || !matches!(kind, RayKind::Camera));
+        let Some(env) = env else {
+            return self.illuminant(ray, self.background);
+        };
+
+        let dir = ray.dir.norm();
+        // the environment was sampled directly at the origin of the ray as well, so this path
+        // only gets its share of the multiple importance sampling weight
+        let weight = match kind {
+            RayKind::Bsdf { pdf } => power_heuristic(pdf, env.pdf(&dir)),
+            _ => 1.0,
+        };
+        self.illuminant(ray, env.radiance(&dir)) * weight
+    }
+
+    // light from the environment at the hit along a sampled direction, weighted against the
+    // material sampling the same direction (see `escaped`)
+    fn sample_environment(
+        &self,
+        env: &EnvironmentMap,
+        ray: &Ray3,
+        hit: &Hit,
+        world: &Arc<dyn Hittable<R>>,
+        rng: &mut R,
+    ) -> Pixel {
+        let Some((dir, light_pdf)) = env.sample(rng) else {
+            return Pixel::zero();
+        };
+        let Some(eval) = hit.mat.eval(ray, hit, &dir) else {
+            return Pixel::zero();
+        };
+        if eval.value.near_zero() {
+            return Pixel::zero();
+        }
+
+        let shadow_ray = ray.spawn(hit.p, dir);
+        let mut t_range = Interval {
+            min: 0.001,
+            max: f64::INFINITY,
+        };
+        if world.hit(&shadow_ray, &mut t_range, rng).is_some() {
+            return Pixel::zero();
+        }
+        let passed = self.pass_weight(&shadow_ray, world, f64::INFINITY, rng);
+        let transmittance = match self.atmosphere.filter(|_| ray.media.is_empty()) {
+            Some(atmosphere) => atmosphere.transmittance(&hit.p, &dir, f64::INFINITY),
+            None => 1.0,
+        };
+
+        let weight = power_heuristic(light_pdf, eval.pdf) * transmittance / light_pdf;
+        &(&self.illuminant(ray, env.radiance(&dir)) * &self.reflectance(ray, eval.value))
+            * &passed
+            * weight
+    }
+
+    // weight of the media that a ray passes up to `t_max` without colliding, see
+    // `Hittable::pass_weight`
+    fn pass_weight(
+        &self,
+        ray: &Ray3,
+        world: &Arc<dyn Hittable<R>>,
+        t_max: f64,
+        rng: &mut R,
+    ) -> Color {
+        if !world.has_pass_weight() {
+            return Color::white();
+        }
+        let t_range = Interval {
+            min: 0.001,
+            max: t_max,
+        };
+        self.reflectance(ray, world.pass_weight(ray, &t_range, rng))
+    }
+
+    // in spectral mode the rgb colours that materials and media attenuate paths with are replaced
+    // by the value of their upsampled spectra at the wavelength of the path
+    fn reflectance(&self, ray: &Ray3, color: Color) -> Color {
+        match ray.wavelength {
+            Some(lambda) if self.spectral => {
+                Color::splat(spectrum::rgb_reflectance(&color, lambda))
+            }
+            _ => color,
+        }
+    }
+
+    // radiance of a light source given as an rgb colour, at the wavelength of the path in spectral
+    // mode
+    fn illuminant(&self, ray: &Ray3, color: Color) -> Color {
+        match ray.wavelength {
+            Some(lambda) if self.spectral => Color::splat(spectrum::rgb_illuminant(&color, lambda)),
+            _ => color,
+        }
+    }
+
+    fn emission(&self, ray: &Ray3, hit: &Hit) -> Color {
+        match ray.wavelength {
+            Some(lambda) if self.spectral => Color::splat(hit.mat.emit_spectral(ray, hit, lambda)),
+            _ => hit.mat.emit(ray, hit),
         }
     }
 
@@ -295,7 +546,7 @@ impl<R: Rng> Camera<R> {
         let dir = (&px_sample - &origin).norm();
         let time = rng.random::<f64>();
 
-        Ray3::with_time(
+        let mut ray = Ray3::with_time(
             if self.defocus_disk_radius <= 0.0 {
                 self.pose.lookfrom.clone()
             } else {
@@ -303,7 +554,12 @@ impl<R: Rng> Camera<R> {
             },
             dir,
             time,
-        )
+        );
+        // the ray covers the angle of a pixel. The samples of a pixel already average over its
+        // area, so their cones get narrower the more of them there are.
+        let samples_scale = (1.0 / (self.rays_per_pixel as f64).sqrt()).max(0.125);
+        ray.cone.spread = self.px_delta_u.len() / (px_sample - origin).len() * samples_scale;
+        ray
     }
 
     fn defocus_disk_sample(&self, rng: &mut R) -> Point {
@@ -322,8 +578,11 @@ impl Camera<ThreadRng> {
         world: Arc<dyn Hittable<ThreadRng>>,
         path: impl AsRef<Path>,
     ) -> RenderResult<()> {
-        let make_rng = |_| rand::rng();
-        self.render_with(world, path, make_rng)
+        self.render_tile(world).save(path)
+    }
+
+    pub fn render_tile(&self, world: Arc<dyn Hittable<ThreadRng>>) -> Tile {
+        self.render_tile_with(world, |_| rand::rng())
     }
 
     pub fn get_rng(&self) -> ThreadRng {
@@ -347,12 +606,20 @@ impl Camera<SmallRng> {
         world: Arc<dyn Hittable<SmallRng>>,
         path: impl AsRef<Path>,
     ) -> RenderResult<()> {
+        self.render_tile(world).save(path)
+    }
+
+    pub fn render_tile(&self, world: Arc<dyn Hittable<SmallRng>>) -> Tile {
         let base_seed = self.rng_base_seed.expect("No RNG seed");
         let make_rng = move |tid| {
             let thread_seed = base_seed.wrapping_add(tid);
             SmallRng::seed_from_u64(thread_seed)
         };
-        self.render_with(world, path, make_rng)
+        self.render_tile_with(world, make_rng)
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng_base_seed = Some(seed);
     }
 
     pub fn get_rng(&self) -> SmallRng {
@@ -360,6 +627,16 @@ impl Camera<SmallRng> {
     }
 }
 
+// weight of a sample with density `pdf` against another strategy sampling the same direction with
+// density `other` (Veach's power heuristic)
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    if other.is_infinite() || pdf <= 0.0 {
+        return 0.0;
+    }
+    let (a, b) = (pdf * pdf, other * other);
+    a / (a + b)
+}
+
 impl Default for Camera<SmallRng> {
     fn default() -> Self {
         Self::new_seeded_rng(CameraIntrinsics::default(), CameraPose::default(), 316u64)
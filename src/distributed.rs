@@ -0,0 +1,321 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    tile::{Region, Tile},
+    vec::Pixel,
+};
+
+// Line based protocol between the coordinator and its workers:
+//
+// coordinator -> worker: `JOB <id> <x> <y> <w> <h> <samples> <seed>` or `DONE`
+// worker -> coordinator: `RESULT <id> <samples>` followed by w * h * 3 little endian f32s (linear rgb)
+//
+// The coordinator sends a new job as soon as a worker connects or returns a result, so workers
+// never sit idle while the queue isn't empty. Workers only get `DONE` once every job has been
+// rendered, since jobs of failed workers are put back into the queue until then.
+
+// how long a worker may take to return the result of a job before it's considered stalled
+const DEFAULT_JOB_TIMEOUT: Duration = Duration::from_secs(600);
+// how often idle workers check the queue for jobs that were put back
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Unit of work handed out by the `Coordinator`: render `samples` rays per pixel of `region`.
+/// Jobs covering the same region are averaged, which allows splitting a frame into sample batches
+/// as well as into tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Job {
+    pub id: u32,
+    pub region: Region,
+    pub samples: u32,
+    // seed for workers using a seeded rng. Every job gets its own seed so that sample batches of
+    // the same region don't produce identical noise.
+    pub seed: u64,
+}
+
+impl Job {
+    /// One job per tile of a `cols` x `rows` grid.
+    pub fn tiles(img_w: u32, img_h: u32, cols: u32, rows: u32, samples: u32) -> Vec<Self> {
+        Region::grid(img_w, img_h, cols, rows)
+            .into_iter()
+            .enumerate()
+            .map(|(id, region)| Self {
+                id: id as u32,
+                region,
+                samples,
+                seed: id as u64,
+            })
+            .collect()
+    }
+
+    /// `batches` jobs that each render the whole image with `samples` rays per pixel.
+    pub fn sample_batches(img_w: u32, img_h: u32, batches: u32, samples: u32) -> Vec<Self> {
+        (0..batches)
+            .map(|id| Self {
+                id,
+                region: Region::full(img_w, img_h),
+                samples,
+                seed: id as u64,
+            })
+            .collect()
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let r = self.region;
+        writeln!(
+            w,
+            "JOB {} {} {} {} {} {} {}",
+            self.id, r.x, r.y, r.w, r.h, self.samples, self.seed
+        )
+    }
+
+    fn parse(line: &str) -> io::Result<Option<Self>> {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("DONE") => return Ok(None),
+            Some("JOB") => {}
+            _ => return Err(invalid_data(format!("unexpected message: {line:?}"))),
+        }
+        let mut next = || -> io::Result<u64> {
+            fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| invalid_data(format!("malformed job: {line:?}")))
+        };
+        Ok(Some(Self {
+            id: next()? as u32,
+            region: Region::new(
+                next()? as u32,
+                next()? as u32,
+                next()? as u32,
+                next()? as u32,
+            ),
+            samples: next()? as u32,
+            seed: next()?,
+        }))
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_result(w: &mut impl Write, id: u32, tile: &Tile) -> io::Result<()> {
+    writeln!(w, "RESULT {} {}", id, tile.samples)?;
+    let mut buf = Vec::with_capacity(tile.pixels.len() * 12);
+    for px in tile.pixels.iter() {
+        for c in px.iter() {
+            buf.extend_from_slice(&(c as f32).to_le_bytes());
+        }
+    }
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+fn read_result(r: &mut impl BufRead, job: &Job) -> io::Result<Tile> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let samples = match fields.as_slice() {
+        ["RESULT", id, samples] if id.parse() == Ok(job.id) => samples
+            .parse()
+            .map_err(|_| invalid_data(format!("malformed result: {line:?}")))?,
+        _ => return Err(invalid_data(format!("unexpected message: {line:?}"))),
+    };
+
+    let mut buf = vec![0u8; job.region.area() * 12];
+    r.read_exact(&mut buf)?;
+    let channel = |i: usize| f32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+
+    let mut tile = Tile::new(job.region, samples);
+    for (i, px) in tile.pixels.iter_mut().enumerate() {
+        *px = Pixel {
+            x: channel(i * 12) as f64,
+            y: channel(i * 12 + 4) as f64,
+            z: channel(i * 12 + 8) as f64,
+        };
+    }
+    Ok(tile)
+}
+
+/// Hands out jobs to workers connecting over TCP and accumulates their results into a single
+/// frame. Jobs of workers that disconnect before returning a result are put back into the queue.
+pub struct Coordinator {
+    listener: TcpListener,
+    img_w: u32,
+    img_h: u32,
+    jobs: Vec<Job>,
+    job_timeout: Option<Duration>,
+}
+
+impl Coordinator {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        img_w: u32,
+        img_h: u32,
+        jobs: Vec<Job>,
+    ) -> io::Result<Self> {
+        let frame = Region::full(img_w, img_h);
+        if let Some(job) = jobs.iter().find(|job| !job.region.fits(&frame)) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "region of job #{} {:?} is outside of the {img_w}x{img_h} frame",
+                    job.id, job.region
+                ),
+            ));
+        }
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            img_w,
+            img_h,
+            jobs,
+            job_timeout: Some(DEFAULT_JOB_TIMEOUT),
+        })
+    }
+
+    /// Sets how long a worker may take for a job before its connection is dropped and the job is
+    /// put back into the queue. `None` waits forever.
+    pub fn set_job_timeout(&mut self, timeout: Option<Duration>) {
+        self.job_timeout = timeout;
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Blocks until every job has been rendered and returns the accumulated frame.
+    pub fn run(self) -> io::Result<Tile> {
+        let total = self.jobs.len();
+        let queue = Arc::new(Mutex::new(VecDeque::from(self.jobs)));
+        // jobs that haven't been rendered yet, including the ones workers are busy with
+        let remaining = Arc::new(AtomicUsize::new(total));
+        let (results, received) = mpsc::channel::<(Job, Tile)>();
+
+        let full = Region::full(self.img_w, self.img_h);
+        let mut sums = vec![Pixel::zero(); full.area()];
+        let mut counts = vec![0u32; full.area()];
+
+        // poll for new workers while collecting results so that we can return as soon as the
+        // last job is done instead of blocking in accept()
+        self.listener.set_nonblocking(true)?;
+        let mut done = 0;
+        while done < total {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    println!("Worker {addr} connected!");
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(self.job_timeout)?;
+                    let queue = queue.clone();
+                    let remaining = remaining.clone();
+                    let results = results.clone();
+                    thread::spawn(move || serve(stream, queue, remaining, results));
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+
+            while let Ok((job, tile)) = received.try_recv() {
+                let r = job.region;
+                for y in 0..r.h {
+                    for x in 0..r.w {
+                        let i = (r.y + y) as usize * full.w as usize + (r.x + x) as usize;
+                        sums[i] = sums[i] + tile.get(x, y) * tile.samples as f64;
+                        counts[i] += tile.samples;
+                    }
+                }
+                done += 1;
+                println!("Job #{} done ({done}/{total})", job.id);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let mut frame = Tile::new(full, counts.iter().copied().min().unwrap_or(0));
+        for (px, (sum, count)) in frame.pixels.iter_mut().zip(sums.iter().zip(counts.iter())) {
+            if *count > 0 {
+                *px = sum / *count as f64;
+            }
+        }
+        Ok(frame)
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    queue: Arc<Mutex<VecDeque<Job>>>,
+    remaining: Arc<AtomicUsize>,
+    results: mpsc::Sender<(Job, Tile)>,
+) {
+    let Ok(read_half) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(read_half);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let job = queue.lock().expect("job queue poisoned").pop_front();
+        let Some(job) = job else {
+            // other workers might still fail and put their jobs back
+            if remaining.load(Ordering::SeqCst) > 0 {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            let _ = writeln!(writer, "DONE").and_then(|_| writer.flush());
+            return;
+        };
+
+        let result = job
+            .write(&mut writer)
+            .and_then(|_| writer.flush())
+            .and_then(|_| read_result(&mut reader, &job));
+
+        match result {
+            Ok(tile) => {
+                remaining.fetch_sub(1, Ordering::SeqCst);
+                if results.send((job, tile)).is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                eprintln!("Worker failed on job #{}: {err}", job.id);
+                queue.lock().expect("job queue poisoned").push_back(job);
+                return;
+            }
+        }
+    }
+}
+
+/// Connects to a `Coordinator` and renders jobs with `render` until the coordinator reports that
+/// all jobs are done.
+pub fn work<F>(addr: impl ToSocketAddrs, mut render: F) -> io::Result<()>
+where
+    F: FnMut(&Job) -> Tile,
+{
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let Some(job) = Job::parse(&line)? else {
+            return Ok(());
+        };
+        let tile = render(&job);
+        write_result(&mut writer, job.id, &tile)?;
+    }
+}
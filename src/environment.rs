@@ -0,0 +1,209 @@
+use core::f64::consts::PI;
+use std::{fmt::Debug, path::Path};
+
+use image::{DynamicImage, ImageError, ImageReader};
+use rand::{Rng, RngCore};
+
+use crate::{
+    texture::{to_linear, Encoding},
+    vec::{Color, Vec3},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentOptions {
+    // rotation of the map around the up (y) axis in degrees
+    pub rotation: f64,
+    // multiplier of the radiance of the map
+    pub intensity: f64,
+    // whether camera rays that miss all objects see the map. Otherwise they see the background
+    // colour of the camera, while the map still lights the scene.
+    pub visible_to_camera: bool,
+}
+
+impl Default for EnvironmentOptions {
+    fn default() -> Self {
+        Self {
+            rotation: 0.0,
+            intensity: 1.0,
+            visible_to_camera: true,
+        }
+    }
+}
+
+/// Light arriving from infinitely far away, given by an equirectangular (latitude-longitude)
+/// panorama, usually an HDR or EXR image. The top row of the image is straight up (+y) and its
+/// centre is seen when looking along -z.
+///
+/// Directions are importance sampled by the luminance of the pixels, so that small and bright
+/// sources like the sun are found by sampling them directly instead of by chance.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    // linear radiance, row by row starting at the top
+    pixels: Vec<Color>,
+    options: EnvironmentOptions,
+    // cdf of picking a row, and for every row the cdf of picking a pixel in it
+    marginal: Vec<f64>,
+    conditional: Vec<f64>,
+    // sum of the sampling weights of all pixels, 0 for a black map
+    total: f64,
+}
+
+impl Debug for EnvironmentMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvironmentMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl EnvironmentMap {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::with_options(path, EnvironmentOptions::default())
+    }
+
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        options: EnvironmentOptions,
+    ) -> Result<Self, ImageError> {
+        let img = ImageReader::open(path)
+            .map_err(ImageError::IoError)?
+            .decode()?;
+        Ok(Self::from_image(img, options))
+    }
+
+    // 8 and 16 bit panoramas are taken as sRGB encoded, float ones (HDR, EXR) as linear
+    pub fn from_image(img: impl Into<DynamicImage>, options: EnvironmentOptions) -> Self {
+        let img = to_linear(img.into(), Encoding::Srgb);
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let pixels: Vec<Color> = img
+            .pixels()
+            .map(|px| Color {
+                x: px.0[0] as f64,
+                y: px.0[1] as f64,
+                z: px.0[2] as f64,
+            })
+            .collect();
+
+        // pixels are picked proportional to their luminance times their solid angle, which
+        // shrinks towards the poles
+        let mut marginal = vec![0.0; height];
+        let mut conditional = vec![0.0; width * height];
+        let mut total = 0.0;
+        for y in 0..height {
+            let sin_theta = row_sin_theta(y, height);
+            let row = &mut conditional[y * width..(y + 1) * width];
+            let mut sum = 0.0;
+            for (x, cdf) in row.iter_mut().enumerate() {
+                sum += luminance(&pixels[y * width + x]) * sin_theta;
+                *cdf = sum;
+            }
+            if sum > 0.0 {
+                row.iter_mut().for_each(|cdf| *cdf /= sum);
+            }
+            total += sum;
+            marginal[y] = total;
+        }
+        if total > 0.0 {
+            marginal.iter_mut().for_each(|cdf| *cdf /= total);
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+            options,
+            marginal,
+            conditional,
+            total,
+        }
+    }
+
+    pub fn visible_to_camera(&self) -> bool {
+        self.options.visible_to_camera
+    }
+
+    /// Radiance arriving from the direction `dir`.
+    pub fn radiance(&self, dir: &Vec3) -> Color {
+        // nearest pixel, which keeps the radiance proportional to the sampling density within a
+        // pixel
+        let (x, y) = self.pixel(self.dir_to_uv(dir));
+        self.pixels[y * self.width + x] * self.options.intensity
+    }
+
+    /// Samples a direction towards the map proportional to the radiance arriving from it. Returns
+    /// the unit direction and its pdf with respect to solid angle, or `None` for a black map.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Option<(Vec3, f64)> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        let (w, h) = (self.width, self.height);
+        let y = pick(&self.marginal, rng.random());
+        let x = pick(&self.conditional[y * w..(y + 1) * w], rng.random());
+
+        // uniformly within the pixel
+        let u = (x as f64 + rng.random::<f64>()) / w as f64;
+        let v = (y as f64 + rng.random::<f64>()) / h as f64;
+        let dir = self.uv_to_dir((u, v));
+        let pdf = self.pdf(&dir);
+        (pdf > 0.0).then_some((dir, pdf))
+    }
+
+    /// Density of `sample` returning the direction `dir` with respect to solid angle.
+    pub fn pdf(&self, dir: &Vec3) -> f64 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        let (u, v) = self.dir_to_uv(dir);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel((u, v));
+        let weight = luminance(&self.pixels[y * self.width + x]) * row_sin_theta(y, self.height);
+
+        // the uv square is mapped onto the sphere with a jacobian of 2π² sin(θ)
+        let pdf_uv = weight / self.total * (self.width * self.height) as f64;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn dir_to_uv(&self, dir: &Vec3) -> (f64, f64) {
+        let dir = dir.norm();
+        let phi = dir.x.atan2(-dir.z) - self.options.rotation.to_radians();
+        let u = (0.5 + phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn uv_to_dir(&self, uv: (f64, f64)) -> Vec3 {
+        let phi = (uv.0 - 0.5) * 2.0 * PI + self.options.rotation.to_radians();
+        let (sin_theta, cos_theta) = (uv.1 * PI).sin_cos();
+        Vec3 {
+            x: sin_theta * phi.sin(),
+            y: cos_theta,
+            z: -sin_theta * phi.cos(),
+        }
+    }
+
+    fn pixel(&self, uv: (f64, f64)) -> (usize, usize) {
+        let x = ((uv.0 * self.width as f64) as usize).min(self.width - 1);
+        let y = ((uv.1 * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+}
+
+// sine of the polar angle at the centre of a row
+fn row_sin_theta(y: usize, height: usize) -> f64 {
+    (PI * (y as f64 + 0.5) / height as f64).sin()
+}
+
+// index of the interval of a normalized cdf that contains u
+fn pick(cdf: &[f64], u: f64) -> usize {
+    cdf.partition_point(|&c| c <= u).min(cdf.len() - 1)
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    aabb::AABB,
+    interval::Interval,
+    ray::{Hit, Hittable, Ray3},
+    texture::Texture,
+};
+
+// upper bound of transparent hits skipped per ray, protects against degenerate geometry that keeps
+// reporting the same hit
+const MAX_SKIPPED_HITS: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub enum AlphaMode {
+    // hits with an alpha below the threshold are fully transparent, all others are opaque. Gives
+    // hard edges, which is what leaves and fences usually want.
+    Threshold(f64),
+    // hits are opaque with a probability equal to their alpha, which averages out to smooth
+    // semi-transparency over many samples
+    Stochastic,
+}
+
+/// Cuts holes into any primitive using the alpha channel of a texture (`Texture::alpha`). Rays
+/// that hit a transparent part of the object continue as if the object wasn't there.
+pub struct AlphaMask<R: Rng, T: Texture> {
+    object: Arc<dyn Hittable<R>>,
+    alpha: T,
+    mode: AlphaMode,
+}
+
+impl<R: Rng, T: Texture> std::fmt::Debug for AlphaMask<R, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlphaMask")
+            .field("object", &self.object)
+            .field("alpha", &self.alpha)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+impl<R: Rng, T: Texture> Hittable<R> for AlphaMask<R, T> {
+    fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
+        let mut range = *t_range;
+
+        for _ in 0..MAX_SKIPPED_HITS {
+            let hit = self.object.hit(ray, &mut range, rng)?;
+            let alpha = self.alpha.alpha(hit.uv, &hit.p);
+            let opaque = match self.mode {
+                AlphaMode::Threshold(threshold) => alpha >= threshold,
+                AlphaMode::Stochastic => rng.random::<f64>() < alpha,
+            };
+            if opaque {
+                return Some(hit);
+            }
+
+            // look for the next hit behind the transparent one (e.g. the back side of a sphere)
+            range = Interval {
+                min: hit.t + 1e-6,
+                max: t_range.max,
+            };
+        }
+
+        None
+    }
+
+    fn bbox(&self) -> AABB {
+        self.object.bbox()
+    }
+}
+
+impl<R: Rng, T: Texture> AlphaMask<R, T> {
+    pub fn new(object: Arc<dyn Hittable<R>>, alpha: T, mode: AlphaMode) -> Self {
+        Self {
+            object,
+            alpha,
+            mode,
+        }
+    }
+
+    pub fn new_arc(object: Arc<dyn Hittable<R>>, alpha: T, mode: AlphaMode) -> Arc<Self> {
+        Arc::new(Self::new(object, alpha, mode))
+    }
+}
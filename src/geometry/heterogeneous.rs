@@ -0,0 +1,284 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    aabb::AABB,
+    geometry::volumetric::{medium_is_active, BoundarySpans},
+    interval::Interval,
+    material::Material,
+    medium::Interior,
+    phase::Phase,
+    ray::{Hit, Hittable, Ray3, Scatter},
+    vec::{Color, Point, Vec3},
+    volume::DensityField,
+};
+
+/// Optical properties of a heterogeneous medium at a density of 1. The actual coefficients at a
+/// point are these scaled by the density field.
+#[derive(Debug, Clone)]
+pub struct VolumeParams {
+    // extinction coefficient per rgb channel. Different values per channel tint the light that
+    // travels through the medium (chromatic extinction).
+    pub sigma_t: Color,
+    // fraction of the extinction that is scattering rather than absorption
+    pub albedo: Color,
+    // radiance emitted by the absorbing particles (fire, explosions). The amount of light a
+    // region adds already grows with its density through the absorption.
+    pub emission: Color,
+    // scales the emission per point, e.g. a temperature field. Without one the emission is the
+    // same everywhere.
+    pub emission_field: Option<Arc<dyn DensityField>>,
+    pub phase: Phase,
+}
+
+impl Default for VolumeParams {
+    fn default() -> Self {
+        Self {
+            sigma_t: Color::splat(1.0),
+            albedo: Color::splat(1.0),
+            emission: Color::zero(),
+            emission_field: None,
+            phase: Phase::Isotropic,
+        }
+    }
+}
+
+/// Participating medium inside of a closed boundary whose density varies in space according to
+/// a `DensityField`.
+///
+/// Collisions are sampled with delta tracking of the mean extinction of the channels, so the
+/// decision whether a ray collides doesn't depend on the channel. Chromatic media weight the
+/// channels relative to that mean (spectral tracking): the collision material carries the ratio of
+/// the coefficients at the collision and `pass_weight` the ratio of the transmittances up to
+/// wherever the ray ends up, which keeps the estimator unbiased for every channel.
+pub struct HeterogeneousMedium<R: Rng> {
+    boundary: Arc<dyn Hittable<R>>,
+    // material of every collision, it reads the coefficients at the hit point
+    collision: Arc<Collision>,
+    // upper bound of the mean extinction anywhere in the medium
+    majorant: f64,
+    // upper bound of how far the extinction of any channel deviates from the mean, 0 for media
+    // that absorb and scatter all channels alike
+    deviation_majorant: f64,
+    container: Option<Interior>,
+}
+
+impl<R: Rng> std::fmt::Debug for HeterogeneousMedium<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeterogeneousMedium")
+            .field("boundary", &self.boundary)
+            .field("density", &self.collision.density)
+            .field("params", &self.collision.params)
+            .field("majorant", &self.majorant)
+            .field("container", &self.container)
+            .finish()
+    }
+}
+
+fn max_channel(c: &Color) -> f64 {
+    c.x.max(c.y).max(c.z)
+}
+
+fn mean(c: &Color) -> f64 {
+    (c.x + c.y + c.z) / 3.0
+}
+
+impl<R: Rng> Hittable<R> for HeterogeneousMedium<R> {
+    fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
+        if self.majorant == 0.0 {
+            return None;
+        }
+        if !medium_is_active(&self.container, ray) {
+            return None;
+        }
+
+        let ray_len = ray.dir.len();
+        let mut spans = BoundarySpans::new();
+        while let Some((entry, exit)) = spans.next_span(self.boundary.as_ref(), ray, t_range, rng) {
+            let mut t = entry;
+            loop {
+                t -= (1.0 - rng.random::<f64>()).ln() / (self.majorant * ray_len);
+                if t >= exit {
+                    break;
+                }
+                // real collision with the probability of the mean extinction, a null collision
+                // otherwise
+                let sigma_t = self.collision.sigma_t(&ray.at(t));
+                if rng.random::<f64>() * self.majorant < mean(&sigma_t) {
+                    return Some(self.collision(ray, t));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn bbox(&self) -> AABB {
+        self.boundary.bbox()
+    }
+
+    fn pass_weight(&self, ray: &Ray3, t_range: &Interval, rng: &mut R) -> Color {
+        if !self.has_pass_weight() || !medium_is_active(&self.container, ray) {
+            return Color::white();
+        }
+
+        // ratio tracking of the deviation of the channels from the mean. Every tentative collision
+        // weights a channel by 1 - (σc - σmean) / majorant, which averages out to the ratio of the
+        // transmittance of the channel to the transmittance `hit` samples collisions with.
+        let ray_len = ray.dir.len();
+        let mut weight = Color::white();
+        let mut spans = BoundarySpans::new();
+        while let Some((entry, exit)) = spans.next_span(self.boundary.as_ref(), ray, t_range, rng) {
+            let mut t = entry;
+            loop {
+                t -= (1.0 - rng.random::<f64>()).ln() / (self.deviation_majorant * ray_len);
+                if t >= exit {
+                    break;
+                }
+                let sigma_t = self.collision.sigma_t(&ray.at(t));
+                let deviation = sigma_t - Color::splat(mean(&sigma_t));
+                weight = &weight * &(Color::white() - deviation / self.deviation_majorant);
+            }
+        }
+        weight
+    }
+
+    fn has_pass_weight(&self) -> bool {
+        self.deviation_majorant > 0.0
+    }
+}
+
+impl<R: Rng> HeterogeneousMedium<R> {
+    pub fn new(
+        boundary: Arc<dyn Hittable<R>>,
+        density: Arc<dyn DensityField>,
+        params: VolumeParams,
+    ) -> Self {
+        let max_density = density.max_density();
+        let sigma_t = params.sigma_t;
+        let mean_sigma_t = mean(&sigma_t);
+        let deviation = Color {
+            x: (sigma_t.x - mean_sigma_t).abs(),
+            y: (sigma_t.y - mean_sigma_t).abs(),
+            z: (sigma_t.z - mean_sigma_t).abs(),
+        };
+        let majorant = max_density * mean_sigma_t;
+        let deviation_majorant = max_density * max_channel(&deviation);
+        // tracking never gets past an infinite majorant, so media without a usable one are
+        // transparent
+        let (majorant, deviation_majorant) =
+            if majorant.is_finite() && majorant > 0.0 && deviation_majorant.is_finite() {
+                (majorant, deviation_majorant)
+            } else {
+                (0.0, 0.0)
+            };
+        Self {
+            majorant,
+            deviation_majorant,
+            boundary,
+            collision: Arc::new(Collision { density, params }),
+            container: None,
+        }
+    }
+
+    /// Places the medium inside of the object with the given interior, like
+    /// `ConstantMedium::inside`.
+    pub fn inside(mut self, interior: Interior) -> Self {
+        self.container = Some(interior);
+        self
+    }
+
+    pub fn new_arc(
+        boundary: Arc<dyn Hittable<R>>,
+        density: Arc<dyn DensityField>,
+        params: VolumeParams,
+    ) -> Arc<Self> {
+        Arc::new(Self::new(boundary, density, params))
+    }
+
+    fn collision(&self, ray: &Ray3, t: f64) -> Hit {
+        let normal = ray.dir.norm() * -1.0;
+        Hit {
+            p: ray.at(t),
+            normal,
+            geo_normal: normal,
+            tangent: Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            bitangent: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            uv: (0.0, 0.0),
+            footprint: 0.0,
+            front_face: true,
+            t,
+            mat: self.collision.clone(),
+        }
+    }
+}
+
+// material of the collisions inside of a heterogeneous medium. Collisions are sampled by the mean
+// extinction, so every channel is weighted by its actual coefficient relative to that mean.
+#[derive(Debug)]
+struct Collision {
+    density: Arc<dyn DensityField>,
+    params: VolumeParams,
+}
+
+impl Collision {
+    fn sigma_t(&self, p: &Point) -> Color {
+        self.params.sigma_t * self.density.density(p)
+    }
+
+    fn emission(&self, p: &Point) -> Color {
+        match &self.params.emission_field {
+            Some(field) => self.params.emission * field.density(p),
+            None => self.params.emission,
+        }
+    }
+
+    // absorption only contributes light if the medium emits, otherwise every collision is treated
+    // as a scattering event weighted by the albedo
+    fn absorb_probability(&self, sigma_t: &Color, emission: &Color) -> f64 {
+        if max_channel(emission) > 0.0 {
+            let sigma_a = *sigma_t - sigma_t * &self.params.albedo;
+            mean(&sigma_a) / mean(sigma_t)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Material for Collision {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let sigma_t = self.sigma_t(&hit.p);
+        let p_absorb = self.absorb_probability(&sigma_t, &self.emission(&hit.p));
+        if rng.random::<f64>() < p_absorb {
+            return None;
+        }
+
+        let sigma_s = &sigma_t * &self.params.albedo;
+        Some(Scatter {
+            attenuation: sigma_s / (mean(&sigma_t) * (1.0 - p_absorb)),
+            scattered_ray: incident_ray
+                .spawn(hit.p, self.params.phase.sample(&incident_ray.dir, rng)),
+        })
+    }
+
+    // only seen by paths that `scatter` absorbed
+    fn emit(&self, _incident_ray: &Ray3, hit: &Hit) -> Color {
+        let sigma_t = self.sigma_t(&hit.p);
+        let emission = self.emission(&hit.p);
+        let p_absorb = self.absorb_probability(&sigma_t, &emission);
+        if p_absorb <= 0.0 {
+            return Color::zero();
+        }
+        let sigma_a = sigma_t - &sigma_t * &self.params.albedo;
+        (&sigma_a * &emission) / (mean(&sigma_t) * p_absorb)
+    }
+}
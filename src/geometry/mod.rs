@@ -1,9 +1,13 @@
+mod alpha_mask;
+mod heterogeneous;
 mod quad;
 mod sphere;
 mod transform;
 mod triangle;
 mod volumetric;
 
+pub use alpha_mask::*;
+pub use heterogeneous::*;
 pub use quad::*;
 pub use sphere::*;
 pub use transform::*;
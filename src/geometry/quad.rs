@@ -18,6 +18,8 @@ pub struct Quad {
     n: Vec3,
     w: Vec3,
     d: f64,
+    // square root of the area the unit uv square covers
+    uv_scale: f64,
     mat: Arc<dyn Material>,
     bbox: AABB,
 }
@@ -38,6 +40,7 @@ impl Quad {
             n: n_norm,
             w,
             d,
+            uv_scale: n.len().sqrt(),
             mat,
             bbox: Self::aabb(q, v, u),
         }
@@ -86,11 +89,14 @@ impl Quad {
             z: max.z - min.z,
         };
 
+        // the normals of all faces point out of the box, which volumes rely on to tell entering
+        // and leaving it apart. Faces whose edges would give an inward normal run u backwards
+        // from the far corner instead, so u and v stay along the same edges with u mirrored.
         quads.add(Quad::new_arc(min + dz, dy, dx, mat.clone())); // front face
-        quads.add(Quad::new_arc(min + dx, dy, dz, mat.clone())); // right face
-        quads.add(Quad::new_arc(min, dy, dx, mat.clone())); // back face
+        quads.add(Quad::new_arc(min + dx + dz, dy, dz * -1.0, mat.clone())); // right face
+        quads.add(Quad::new_arc(min + dx, dy, dx * -1.0, mat.clone())); // back face
         quads.add(Quad::new_arc(min, dy, dz, mat.clone())); // left face
-        quads.add(Quad::new_arc(min + dy, dz, dx, mat.clone())); // top face
+        quads.add(Quad::new_arc(min + dy + dx, dz, dx * -1.0, mat.clone())); // top face
         quads.add(Quad::new_arc(min, dz, dx, mat.clone())); // bottom face
 
         quads
@@ -133,7 +139,11 @@ impl<R: Rng> Hittable<R> for Quad {
             mat: self.mat.clone(),
             front_face,
             normal: if front_face { self.n } else { self.n * -1.0 },
+            geo_normal: if front_face { self.n } else { self.n * -1.0 },
+            tangent: self.u.norm(),
+            bitangent: self.v.norm(),
             uv: (alpha, beta),
+            footprint: ray.footprint(t, &self.n) / self.uv_scale,
         })
     }
 
@@ -8,7 +8,7 @@ use crate::{
     interval::Interval,
     material::Material,
     ray::{Hit, Hittable, Ray3},
-    vec::{Point, Vec3},
+    vec::{Onb, Point, Vec3},
 };
 
 #[derive(Debug)]
@@ -117,16 +117,35 @@ impl<R: Rng> Hittable<R> for Sphere {
         // surface's inherent outward normal. A back face hit occurs when they are generally
         // in the same direction (meaning the ray is inside the object and trying to exit)
         let front_face = ray.dir.dot(&outward_normal) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            outward_normal * -1.0
+        };
+
+        // u follows the azimuth around the y-axis, v the polar angle from the south to the north
+        // pole. At the poles the azimuth is undefined, so any tangent will do.
+        let tangent = Vec3 {
+            x: outward_normal.z,
+            y: 0.0,
+            z: -outward_normal.x,
+        };
+        let tangent = if tangent.near_zero() {
+            Onb::from_w(&outward_normal).u
+        } else {
+            tangent.norm()
+        };
 
         return Some(Hit {
             p: intersection_point.clone(),
             t: eval,
             uv: self.get_uv(&outward_normal),
-            normal: if front_face {
-                outward_normal
-            } else {
-                outward_normal * -1.0
-            },
+            // the uv square is stretched over the 4πr² of the surface
+            footprint: ray.footprint(eval, &normal) / (2.0 * self.radius.abs() * PI.sqrt()),
+            normal,
+            geo_normal: normal,
+            tangent,
+            bitangent: outward_normal.cross(&tangent),
             front_face,
             mat: self.mat.clone(),
         });
@@ -6,7 +6,7 @@ use crate::{
     aabb::AABB,
     interval::Interval,
     ray::{Hit, Hittable, Ray3},
-    vec::{Point, Vec3},
+    vec::{Color, Point, Vec3},
 };
 
 #[derive(Debug)]
@@ -34,8 +34,7 @@ impl<R: Rng> std::fmt::Debug for Translate<R> {
 
 impl<R: Rng> Hittable<R> for Translate<R> {
     fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
-        let origin = ray.origin - self.offset;
-        let translated_ray = Ray3::with_time(origin, ray.dir, ray.time);
+        let translated_ray = ray.spawn(ray.origin - self.offset, ray.dir);
 
         if let Some(mut hit) = self.object.hit(&translated_ray, t_range, rng) {
             hit.p = hit.p + self.offset;
@@ -48,6 +47,15 @@ impl<R: Rng> Hittable<R> for Translate<R> {
     fn bbox(&self) -> AABB {
         self.bbox
     }
+
+    fn pass_weight(&self, ray: &Ray3, t_range: &Interval, rng: &mut R) -> Color {
+        let translated_ray = ray.spawn(ray.origin - self.offset, ray.dir);
+        self.object.pass_weight(&translated_ray, t_range, rng)
+    }
+
+    fn has_pass_weight(&self) -> bool {
+        self.object.has_pass_weight()
+    }
 }
 
 impl<R: Rng> Translate<R> {
@@ -86,49 +94,33 @@ impl<R: Rng> std::fmt::Debug for Rotate<R> {
 
 impl<R: Rng> Hittable<R> for Rotate<R> {
     fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
-        // math for rotating about the y-axis:
-        // rotate the incident ray by -θ (inverse rotation) since we rotate the ray rather than the geometry itself
-        // we rotate in the xz-plane starting from the positive x-axis
-        // x = r * cos(α)
-        // z = r * sin(α)
-        // x' = r * cos(α - θ) = r * (cos(α)cos(θ) + sin(α)sin(θ)) = x * cos(θ) + z * sin(θ)
-        // y' = y
-        // z' = r * sin(α - θ) = r * (sin(α)cos(θ) - sin(θ)cos(α)) = z * cos(θ) - x * sin(θ)
-
-        // pass in -sin_theta for inverse rotation
-        let (origin, dir) = match self.axis {
-            Axis::X => (
-                ray.origin.rot_x(self.cos_theta, -self.sin_theta),
-                ray.dir.rot_x(self.cos_theta, -self.sin_theta),
-            ),
-            Axis::Y => (
-                ray.origin.rot_y(self.cos_theta, -self.sin_theta),
-                ray.dir.rot_y(self.cos_theta, -self.sin_theta),
-            ),
-            Axis::Z => (
-                ray.origin.rot_z(self.cos_theta, -self.sin_theta),
-                ray.dir.rot_z(self.cos_theta, -self.sin_theta),
-            ),
-        };
-
-        let rotated_ray = Ray3::with_time(origin, dir, ray.time);
+        let rotated_ray = self.to_object_space(ray);
 
         if let Some(mut hit) = self.object.hit(&rotated_ray, t_range, rng) {
-            // rotate back the intersection point and surface normal (positive sin_theta)
+            // rotate back the intersection point and surface frame (positive sin_theta)
             // mathematically rotation doesn't change the length of a vector but for robustness sake (floating
             // point inaccuracies) we normalize the surface normal again
             match self.axis {
                 Axis::X => {
                     hit.p = hit.p.rot_x(self.cos_theta, self.sin_theta);
                     hit.normal = hit.normal.rot_x(self.cos_theta, self.sin_theta).norm();
+                    hit.geo_normal = hit.geo_normal.rot_x(self.cos_theta, self.sin_theta).norm();
+                    hit.tangent = hit.tangent.rot_x(self.cos_theta, self.sin_theta).norm();
+                    hit.bitangent = hit.bitangent.rot_x(self.cos_theta, self.sin_theta).norm();
                 }
                 Axis::Y => {
                     hit.p = hit.p.rot_y(self.cos_theta, self.sin_theta);
                     hit.normal = hit.normal.rot_y(self.cos_theta, self.sin_theta).norm();
+                    hit.geo_normal = hit.geo_normal.rot_y(self.cos_theta, self.sin_theta).norm();
+                    hit.tangent = hit.tangent.rot_y(self.cos_theta, self.sin_theta).norm();
+                    hit.bitangent = hit.bitangent.rot_y(self.cos_theta, self.sin_theta).norm();
                 }
                 Axis::Z => {
                     hit.p = hit.p.rot_z(self.cos_theta, self.sin_theta);
                     hit.normal = hit.normal.rot_z(self.cos_theta, self.sin_theta).norm();
+                    hit.geo_normal = hit.geo_normal.rot_z(self.cos_theta, self.sin_theta).norm();
+                    hit.tangent = hit.tangent.rot_z(self.cos_theta, self.sin_theta).norm();
+                    hit.bitangent = hit.bitangent.rot_z(self.cos_theta, self.sin_theta).norm();
                 }
             }
             Some(hit)
@@ -140,6 +132,15 @@ impl<R: Rng> Hittable<R> for Rotate<R> {
     fn bbox(&self) -> AABB {
         self.bbox
     }
+
+    fn pass_weight(&self, ray: &Ray3, t_range: &Interval, rng: &mut R) -> Color {
+        self.object
+            .pass_weight(&self.to_object_space(ray), t_range, rng)
+    }
+
+    fn has_pass_weight(&self) -> bool {
+        self.object.has_pass_weight()
+    }
 }
 
 impl<R: Rng> Rotate<R> {
@@ -198,4 +199,33 @@ impl<R: Rng> Rotate<R> {
     pub fn new_arc(object: Arc<dyn Hittable<R>>, angle: f64, axis: Axis) -> Arc<Self> {
         Arc::from(Self::new(object, angle, axis))
     }
+
+    fn to_object_space(&self, ray: &Ray3) -> Ray3 {
+        // math for rotating about the y-axis:
+        // rotate the incident ray by -θ (inverse rotation) since we rotate the ray rather than the geometry itself
+        // we rotate in the xz-plane starting from the positive x-axis
+        // x = r * cos(α)
+        // z = r * sin(α)
+        // x' = r * cos(α - θ) = r * (cos(α)cos(θ) + sin(α)sin(θ)) = x * cos(θ) + z * sin(θ)
+        // y' = y
+        // z' = r * sin(α - θ) = r * (sin(α)cos(θ) - sin(θ)cos(α)) = z * cos(θ) - x * sin(θ)
+
+        // pass in -sin_theta for inverse rotation
+        let (origin, dir) = match self.axis {
+            Axis::X => (
+                ray.origin.rot_x(self.cos_theta, -self.sin_theta),
+                ray.dir.rot_x(self.cos_theta, -self.sin_theta),
+            ),
+            Axis::Y => (
+                ray.origin.rot_y(self.cos_theta, -self.sin_theta),
+                ray.dir.rot_y(self.cos_theta, -self.sin_theta),
+            ),
+            Axis::Z => (
+                ray.origin.rot_z(self.cos_theta, -self.sin_theta),
+                ray.dir.rot_z(self.cos_theta, -self.sin_theta),
+            ),
+        };
+
+        ray.spawn(origin, dir)
+    }
 }
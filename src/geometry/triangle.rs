@@ -18,6 +18,8 @@ pub struct Triangle {
     n: Vec3,
     w: Vec3,
     d: f64,
+    // square root of the area the unit uv square covers
+    uv_scale: f64,
     mat: Arc<dyn Material>,
     bbox: AABB,
 }
@@ -38,6 +40,7 @@ impl Triangle {
             n: n_norm,
             w,
             d,
+            uv_scale: n.len().sqrt(),
             mat,
             bbox: Self::aabb(q, v, u),
         }
@@ -86,7 +89,11 @@ impl<R: Rng> Hittable<R> for Triangle {
             mat: self.mat.clone(),
             front_face,
             normal: if front_face { self.n } else { self.n * -1.0 },
+            geo_normal: if front_face { self.n } else { self.n * -1.0 },
+            tangent: self.u.norm(),
+            bitangent: self.v.norm(),
             uv: (alpha, beta),
+            footprint: ray.footprint(t, &self.n) / self.uv_scale,
         })
     }
 
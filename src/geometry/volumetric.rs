@@ -7,6 +7,7 @@ use crate::{
     aabb::AABB,
     interval::Interval,
     material::Material,
+    medium::Interior,
     ray::{Hit, Hittable, Ray3},
     vec::Vec3,
 };
@@ -15,6 +16,7 @@ pub struct ConstantMedium<R: Rng> {
     boundary: Arc<dyn Hittable<R>>,
     neg_inv_density: f64,
     phase_function: Arc<dyn Material>,
+    container: Option<Interior>,
 }
 
 impl<R: Rng> std::fmt::Debug for ConstantMedium<R> {
@@ -23,55 +25,135 @@ impl<R: Rng> std::fmt::Debug for ConstantMedium<R> {
             .field("boundary", &self.boundary)
             .field("neg_inv_density", &self.neg_inv_density)
             .field("phase", &self.phase_function)
+            .field("container", &self.container)
             .field("rng", &"<hidden>")
             .finish()
     }
 }
 
-impl<R: Rng> Hittable<R> for ConstantMedium<R> {
-    fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
-        let mut entry = match self.boundary.hit(ray, &mut Interval::universe(), rng) {
-            Some(hit) => hit,
-            None => return None,
-        };
-
-        let mut exit = match self.boundary.hit(
-            ray,
-            &mut Interval {
-                min: entry.t + 0.0001,
-                max: f64::INFINITY,
-            },
-            rng,
-        ) {
-            Some(hit) => hit,
-            None => return None,
-        };
+// maximum number of boundary crossings considered along a single ray, guards against boundaries
+// that are hit over and over at the same distance
+const MAX_CROSSINGS: usize = 64;
+
+/// Walks the parts of a ray that lie inside the boundary of a medium, front to back.
+///
+/// Every crossing of the boundary changes the depth by one: front face hits enter it and back face
+/// hits leave it, so boundaries only need consistently outward facing normals. This makes concave
+/// boundaries and unions of overlapping closed objects work, and since the walk starts at the very
+/// beginning of the ray's line a ray starting inside of the medium (a camera in fog) is handled too.
+pub(crate) struct BoundarySpans {
+    t: f64,
+    depth: usize,
+    crossings: usize,
+}
 
-        if entry.t < t_range.min {
-            entry.t = t_range.min
-        };
-        if exit.t > t_range.max {
-            exit.t = t_range.max
-        };
+impl BoundarySpans {
+    pub(crate) fn new() -> Self {
+        Self {
+            t: f64::NEG_INFINITY,
+            depth: 0,
+            crossings: 0,
+        }
+    }
 
-        if entry.t >= exit.t {
-            return None;
+    // next span (entry, exit) of the ray inside of the boundary, clipped to t_range
+    pub(crate) fn next_span<R: Rng>(
+        &mut self,
+        boundary: &dyn Hittable<R>,
+        ray: &Ray3,
+        t_range: &Interval,
+        rng: &mut R,
+    ) -> Option<(f64, f64)> {
+        let mut entry = self.t;
+        let mut last_exit = None;
+        while self.crossings < MAX_CROSSINGS {
+            let hit = boundary.hit(
+                ray,
+                &mut Interval {
+                    min: self.t,
+                    max: f64::INFINITY,
+                },
+                rng,
+            );
+            let Some(hit) = hit else { break };
+
+            // nothing beyond the end of the range matters
+            if hit.t >= t_range.max {
+                self.crossings = MAX_CROSSINGS;
+                let inside = self.depth > 0;
+                self.depth = 0;
+                return inside
+                    .then(|| Self::clip(entry, t_range.max, t_range))
+                    .flatten();
+            }
+
+            self.crossings += 1;
+            // hits at (almost) the same distance, e.g. on the edge between two quads of a box, are
+            // a single crossing
+            self.t = hit.t + 0.0001;
+            if hit.front_face {
+                if self.depth == 0 {
+                    entry = hit.t;
+                }
+                self.depth += 1;
+                last_exit = None;
+            } else if self.depth > 0 {
+                self.depth -= 1;
+                last_exit = Some(hit.t);
+                if self.depth == 0 {
+                    if let Some(span) = Self::clip(entry, hit.t, t_range) {
+                        return Some(span);
+                    }
+                }
+            }
         }
 
-        if entry.t < 0.0 {
-            entry.t = 0.0
+        // there is no boundary left in front of the ray. Coinciding surfaces of different objects
+        // (two boxes stacked on top of each other) count as a single crossing, so the depth can
+        // still be positive even though the ray has left through the last of them.
+        self.crossings = MAX_CROSSINGS;
+        if self.depth > 0 {
+            self.depth = 0;
+            return Self::clip(entry, last_exit.unwrap_or(t_range.max), t_range);
         }
+        None
+    }
 
-        let ray_len = ray.dir.len();
-        let dist_in_boundary = (exit.t - entry.t) * ray_len;
-        let hit_dist = self.neg_inv_density * rng.random::<f64>().ln();
+    fn clip(entry: f64, exit: f64, t_range: &Interval) -> Option<(f64, f64)> {
+        let entry = entry.max(t_range.min).max(0.0);
+        let exit = exit.min(t_range.max);
+        (entry < exit).then_some((entry, exit))
+    }
+}
 
-        // hit occured outside of medium's boundary
-        if hit_dist > dist_in_boundary {
+/// Whether a medium that lives inside of the object with the interior `container` (or outside of
+/// all objects for `None`) exists where the ray currently travels. Objects with an interior (glass,
+/// subsurface materials) put it on the medium stack of the rays refracted into them, so e.g. fog
+/// doesn't fill the inside of a glass sphere standing in it, while smoke inside of the glass does.
+pub(crate) fn medium_is_active(container: &Option<Interior>, ray: &Ray3) -> bool {
+    ray.media.top() == container.as_ref()
+}
+
+impl<R: Rng> Hittable<R> for ConstantMedium<R> {
+    fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit> {
+        if !medium_is_active(&self.container, ray) {
             return None;
         }
 
-        let t = entry.t + hit_dist / ray_len;
+        let ray_len = ray.dir.len();
+        let mut hit_dist = self.neg_inv_density * rng.random::<f64>().ln();
+
+        // the sampled distance is spent on the parts of the ray inside of the boundary one after
+        // the other. If they are all shorter the hit occured outside of the medium's boundary.
+        let mut spans = BoundarySpans::new();
+        let t = loop {
+            let (entry, exit) = spans.next_span(self.boundary.as_ref(), ray, t_range, rng)?;
+            let dist_in_boundary = (exit - entry) * ray_len;
+            if hit_dist <= dist_in_boundary {
+                break entry + hit_dist / ray_len;
+            }
+            hit_dist -= dist_in_boundary;
+        };
 
         Some(Hit {
             p: ray.at(t),
@@ -80,7 +162,23 @@ impl<R: Rng> Hittable<R> for ConstantMedium<R> {
                 y: 0.0,
                 z: 0.0,
             },
+            geo_normal: Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            tangent: Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            bitangent: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
             uv: (1.0, 1.0),
+            footprint: 0.0,
             front_face: true,
             t,
             mat: self.phase_function.clone(),
@@ -102,9 +200,18 @@ impl<R: Rng> ConstantMedium<R> {
             boundary,
             neg_inv_density: -1.0 / density,
             phase_function,
+            container: None,
         }
     }
 
+    /// Places the medium inside of the object with the given interior (e.g. smoke inside of a
+    /// glass). By default a medium lives outside of all objects and doesn't fill objects with an
+    /// interior that are placed in it.
+    pub fn inside(mut self, interior: Interior) -> Self {
+        self.container = Some(interior);
+        self
+    }
+
     pub fn new_arc(
         boundary: Arc<dyn Hittable<R>>,
         density: f64,
@@ -1,10 +1,18 @@
 pub mod aabb;
 pub mod bvh;
 pub mod camera;
+pub mod distributed;
+pub mod environment;
 pub mod geometry;
 pub mod interval;
 pub mod material;
+pub mod medium;
+pub mod noise;
+pub mod phase;
 pub mod ray;
+pub mod spectrum;
 pub mod texture;
+pub mod tile;
 pub mod utils;
 pub mod vec;
+pub mod volume;
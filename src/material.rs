@@ -1,17 +1,52 @@
+mod conductor;
+mod diffuse;
+mod layered;
+mod mapping;
+mod microfacet;
+mod principled;
+mod rough_dielectric;
+mod subsurface;
+
+pub use conductor::*;
+pub use diffuse::*;
+pub use layered::*;
+pub use mapping::*;
+pub use principled::*;
+pub use rough_dielectric::*;
+pub use subsurface::*;
+
+use core::f64::consts::PI;
+
 use rand::{Rng, RngCore};
 use std::{fmt::Debug, sync::Arc};
 
 use crate::{
-    ray::{Hit, Ray3, Scatter},
+    medium::Interior,
+    phase::Phase,
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    spectrum,
     texture::Texture,
-    vec::{Color, Point, Vec3},
+    vec::{Color, Vec3},
 };
 
 pub trait Material: Debug + Send + Sync {
     fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter>;
-    fn emit(&self, _uv: (f64, f64), _p: &Point) -> Color {
+    // radiance emitted at the hit towards the origin of the incident ray
+    fn emit(&self, _incident_ray: &Ray3, _hit: &Hit) -> Color {
         Color::zero()
     }
+    // emitted radiance at `lambda` nm for the spectral renderer
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        spectrum::rgb_illuminant(&self.emit(incident_ray, hit), lambda)
+    }
+    // value and pdf of scattering the incident ray into `dir`, which lets the renderer sample
+    // lights directly. Materials with perfectly specular lobes (`Dielectric`, `Metal` without fuzz,
+    // smooth `Conductor` and `RoughDielectric`) and the ones whose lobes are picked without a
+    // closed form density (`Principled`, `Coated`, `Subsurface`) can only be sampled and return None. `Mix` is
+    // only evaluated if both of its materials are.
+    fn eval(&self, _incident_ray: &Ray3, _hit: &Hit, _dir: &Vec3) -> Option<BsdfEval> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,8 +69,16 @@ impl<T: Texture> Material for Lambertian<T> {
         }
 
         Some(Scatter {
-            attenuation: self.tex.value(hit.uv, &hit.p),
-            scattered_ray: Ray3::with_time(hit.p, reflection_dir, incident_ray.time),
+            attenuation: self.tex.filtered(hit.uv, &hit.p, hit.footprint),
+            scattered_ray: incident_ray.spawn(hit.p, reflection_dir),
+        })
+    }
+
+    fn eval(&self, _incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let cos_theta = hit.normal.dot(&dir.norm()).max(0.0);
+        Some(BsdfEval {
+            value: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * (cos_theta / PI),
+            pdf: cos_theta / PI,
         })
     }
 }
@@ -55,25 +98,145 @@ impl<T: Texture> Metal<T> {
 impl<T: Texture> Material for Metal<T> {
     fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
         Some(Scatter {
-            attenuation: self.tex.value(hit.uv, &hit.p),
-            scattered_ray: Ray3::with_time(
+            attenuation: self.tex.filtered(hit.uv, &hit.p, hit.footprint),
+            scattered_ray: incident_ray.spawn(
                 hit.p,
                 incident_ray.dir.norm().reflect(&hit.normal)
                     + (Vec3::rand_unit_sphere_vec(rng) * self.fuzz),
-                incident_ray.time,
             ),
         })
     }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        // a perfect mirror can only be sampled
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+
+        // scattered directions point at a uniformly picked point on the sphere of radius fuzz
+        // around the mirrored direction. The density of a direction sums the area density of the
+        // points the direction passes through, converted to solid angle by t² / |cos|.
+        let reflected = incident_ray.dir.norm().reflect(&hit.normal);
+        let dir = dir.norm();
+        let b = dir.dot(&reflected);
+        let discriminant = b * b - 1.0 + self.fuzz * self.fuzz;
+        let mut pdf = 0.0;
+        if discriminant > 0.0 {
+            let root = discriminant.sqrt();
+            for t in [b - root, b + root] {
+                if t > 0.0 {
+                    pdf += t * t / (4.0 * PI * self.fuzz * root);
+                }
+            }
+        }
+
+        Some(BsdfEval {
+            value: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * pdf,
+            pdf,
+        })
+    }
+}
+
+/// Refractive index of a dielectric, optionally depending on the wavelength of the light.
+/// Wavelengths are passed in nm, the coefficients of the dispersion formulas use µm like most
+/// published tables do.
+#[derive(Debug, Clone, Copy)]
+pub enum Ior {
+    Constant(f64),
+    // n(λ) = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n²(λ) = 1 + Σ b_i * λ² / (λ² - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Ior {
+    // sodium d-line, the wavelength that a single "refractive index of X" usually refers to
+    const REFERENCE_WAVELENGTH: f64 = 589.3;
+
+    pub fn at(&self, wavelength: f64) -> f64 {
+        let um = wavelength / 1000.0;
+        let um2 = um * um;
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / um2,
+            Ior::Sellmeier { b, c } => (1.0
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * um2 / (um2 - c))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    // Schott N-BK7 crown glass
+    pub fn bk7() -> Self {
+        Ior::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    // Schott SF11 dense flint glass, disperses a lot more than bk7
+    pub fn sf11() -> Self {
+        Ior::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.1750 * 0.1750, 0.1060 * 0.1060, 0.0],
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Dielectric {
-    refractive_index: f64,
+    ior: Ior,
+    interior: Interior,
 }
 
 impl Dielectric {
     pub fn new(refractive_index: f64) -> Arc<Self> {
-        Arc::from(Self { refractive_index })
+        Self::absorbing(refractive_index, Color::zero())
+    }
+
+    // coloured glass/liquid: light travelling through the inside is attenuated per unit distance by
+    // exp(-absorption)
+    pub fn absorbing(refractive_index: f64, absorption: Color) -> Arc<Self> {
+        Arc::from(Self {
+            ior: Ior::Constant(refractive_index),
+            interior: Interior::new(absorption),
+        })
+    }
+
+    // coloured glass/liquid specified by the colour that's left of white light after travelling
+    // `distance` units through it, which is more intuitive to pick than absorption coefficients
+    pub fn tinted(refractive_index: f64, color: Color, distance: f64) -> Arc<Self> {
+        Arc::from(Self {
+            ior: Ior::Constant(refractive_index),
+            interior: Interior::from_transmittance(color, distance),
+        })
+    }
+
+    // glass that splits white light into its spectrum (prisms, diamonds)
+    pub fn dispersive(ior: Ior) -> Arc<Self> {
+        Arc::from(Self {
+            ior,
+            interior: Interior::new(Color::zero()),
+        })
+    }
+
+    // the interior rays refracted into the object carry on their medium stack, e.g. to put smoke
+    // inside of the glass with `ConstantMedium::inside`
+    pub fn interior(&self) -> Interior {
+        self.interior
     }
 }
 
@@ -90,11 +253,31 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // a dispersive material refracts every wavelength differently, so the path has to commit
+        // to a single wavelength. Paths that haven't done so yet sample one here and get weighted
+        // by its rgb colour.
+        let (refractive_index, wavelength, attenuation) = match incident_ray.wavelength {
+            _ if !self.ior.is_dispersive() => (
+                self.ior.at(Ior::REFERENCE_WAVELENGTH),
+                incident_ray.wavelength,
+                Color::white(),
+            ),
+            Some(wavelength) => (self.ior.at(wavelength), Some(wavelength), Color::white()),
+            None => {
+                let wavelength = spectrum::sample_wavelength(rng);
+                (
+                    self.ior.at(wavelength),
+                    Some(wavelength),
+                    spectrum::wavelength_weight(wavelength),
+                )
+            }
+        };
+
         let refraction_ratio = if hit.front_face {
-            1.0 / self.refractive_index // we assume that the outside medium is air which has a
-                                        // refractive index of ~1
+            1.0 / refractive_index // we assume that the outside medium is air which has a
+                                   // refractive index of ~1
         } else {
-            self.refractive_index
+            refractive_index
         };
         let unit_dir = incident_ray.dir.norm();
 
@@ -104,33 +287,98 @@ impl Material for Dielectric {
         // sin(theta') can't be bigger than 1, so if that is the case (or schlicks's) we need to reflect the ray
         // instead
 
-        let dir = if refraction_ratio * sin_theta > 1.0
-            || Self::reflectance(refraction_ratio, cos_theta) > rng.random()
-        {
+        let reflect = refraction_ratio * sin_theta > 1.0
+            || Self::reflectance(refraction_ratio, cos_theta) > rng.random();
+
+        let dir = if reflect {
             unit_dir.reflect(&hit.normal)
         } else {
             unit_dir.refract(&hit.normal, refraction_ratio)
         };
 
+        let mut scattered_ray = incident_ray.spawn(hit.p, dir);
+        scattered_ray.wavelength = wavelength;
+        // keep track of whether the ray is inside the glass so that the camera can apply the
+        // absorption along the distance travelled inside of it
+        if !reflect {
+            if hit.front_face {
+                scattered_ray.media.push(self.interior);
+            } else {
+                scattered_ray.media.remove(&self.interior);
+            }
+        }
+
         Some(Scatter {
-            attenuation: Color {
-                x: 1.0,
-                y: 1.0,
-                z: 1.0,
-            },
-            scattered_ray: Ray3::with_time(hit.p, dir, incident_ray.time),
+            attenuation,
+            scattered_ray,
         })
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterOptions {
+    // scales the texture colour. The product is the emitted radiance, i.e. W/(sr·m²) for scenes
+    // modelled in metres (or the luminance in cd/m² for a `SpectrumTex`)
+    pub intensity: f64,
+    // only emit from the front face (the side the geometric normal of the primitive points to)
+    pub one_sided: bool,
+    // half angle of the cone around the normal that light is emitted into in degrees. 90 emits into
+    // the whole hemisphere like a diffuse panel, smaller angles give a spot light.
+    pub cone_angle: f64,
+    // width of the smooth falloff at the edge of the cone in degrees
+    pub cone_softness: f64,
+}
+
+impl Default for EmitterOptions {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            one_sided: false,
+            cone_angle: 90.0,
+            cone_softness: 0.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Emitter<T: Texture> {
     tex: T,
+    options: EmitterOptions,
 }
 
 impl<T: Texture> Emitter<T> {
     pub fn new(tex: T) -> Arc<Self> {
-        Arc::from(Self { tex })
+        Self::with_options(tex, EmitterOptions::default())
+    }
+
+    pub fn with_options(tex: T, options: EmitterOptions) -> Arc<Self> {
+        Arc::from(Self { tex, options })
+    }
+
+    // fraction of the radiance emitted towards the origin of the incident ray
+    fn falloff(&self, incident_ray: &Ray3, hit: &Hit) -> f64 {
+        if self.options.one_sided && !hit.front_face {
+            return 0.0;
+        }
+        if self.options.cone_angle >= 90.0 {
+            return 1.0;
+        }
+
+        let cos_theta = -incident_ray.dir.norm().dot(&hit.geo_normal);
+        let outer = self.options.cone_angle.to_radians().cos();
+        let inner = (self.options.cone_angle - self.options.cone_softness)
+            .max(0.0)
+            .to_radians()
+            .cos();
+        if cos_theta >= inner {
+            1.0
+        } else if cos_theta <= outer {
+            0.0
+        } else {
+            // smoothstep
+            let t = (cos_theta - outer) / (inner - outer);
+            t * t * (3.0 - 2.0 * t)
+        }
     }
 }
 
@@ -138,8 +386,19 @@ impl<T: Texture> Material for Emitter<T> {
     fn scatter(&self, _incident_ray: &Ray3, _hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scatter> {
         None
     }
-    fn emit(&self, uv: (f64, f64), p: &Point) -> Color {
-        self.tex.value(uv, p)
+    fn emit(&self, incident_ray: &Ray3, hit: &Hit) -> Color {
+        let scale = self.options.intensity * self.falloff(incident_ray, hit);
+        if scale <= 0.0 {
+            return Color::zero();
+        }
+        self.tex.filtered(hit.uv, &hit.p, hit.footprint) * scale
+    }
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        let scale = self.options.intensity * self.falloff(incident_ray, hit);
+        if scale <= 0.0 {
+            return 0.0;
+        }
+        self.tex.spectral_emission(hit.uv, &hit.p, lambda) * scale
     }
 }
 
@@ -157,12 +416,48 @@ impl<T: Texture> Isotropic<T> {
 impl<T: Texture> Material for Isotropic<T> {
     fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
         Some(Scatter {
-            attenuation: self.tex.value(hit.uv, &hit.p),
-            scattered_ray: Ray3::with_time(
-                hit.p,
-                Vec3::rand_unit_sphere_vec(rng),
-                incident_ray.time,
-            ),
+            attenuation: self.tex.filtered(hit.uv, &hit.p, hit.footprint),
+            scattered_ray: incident_ray.spawn(hit.p, Vec3::rand_unit_sphere_vec(rng)),
+        })
+    }
+
+    fn eval(&self, _incident_ray: &Ray3, hit: &Hit, _dir: &Vec3) -> Option<BsdfEval> {
+        let phase = Phase::Isotropic.eval(0.0);
+        Some(BsdfEval {
+            value: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * phase,
+            pdf: phase,
+        })
+    }
+}
+
+/// Phase function material for participating media (e.g. the `phase_function` of a
+/// `ConstantMedium`) that scatters according to any of the `Phase` distributions instead of
+/// uniformly like `Isotropic`.
+#[derive(Debug, Clone)]
+pub struct Anisotropic<T: Texture> {
+    tex: T,
+    phase: Phase,
+}
+
+impl<T: Texture> Anisotropic<T> {
+    pub fn new(tex: T, phase: Phase) -> Arc<Self> {
+        Arc::from(Self { tex, phase })
+    }
+}
+
+impl<T: Texture> Material for Anisotropic<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        Some(Scatter {
+            attenuation: self.tex.filtered(hit.uv, &hit.p, hit.footprint),
+            scattered_ray: incident_ray.spawn(hit.p, self.phase.sample(&incident_ray.dir, rng)),
+        })
+    }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let phase = self.phase.eval(incident_ray.dir.norm().dot(&dir.norm()));
+        Some(BsdfEval {
+            value: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * phase,
+            pdf: phase,
         })
     }
 }
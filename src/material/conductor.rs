@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{
+    material::{
+        microfacet::{fresnel_conductor, reflect, Ggx},
+        Material,
+    },
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    vec::{Color, Onb, Vec3},
+};
+
+/// Physically based metal using a GGX microfacet distribution with Smith masking-shadowing and
+/// the exact fresnel reflectance of a complex index of refraction `eta + ik` (per rgb channel).
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    ggx: Ggx,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Arc<Self> {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    // roughness_u and roughness_v stretch the highlight along the two tangent directions of the
+    // surface (brushed metal)
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Arc<Self> {
+        Arc::from(Self {
+            eta,
+            k,
+            ggx: Ggx::new(roughness_u, roughness_v),
+        })
+    }
+
+    // rgb complex indices of refraction sampled at ~650nm, ~550nm and ~450nm
+
+    pub fn gold(roughness: f64) -> Arc<Self> {
+        Self::new(
+            Color {
+                x: 0.143,
+                y: 0.374,
+                z: 1.442,
+            },
+            Color {
+                x: 3.983,
+                y: 2.386,
+                z: 1.603,
+            },
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Arc<Self> {
+        Self::new(
+            Color {
+                x: 0.200,
+                y: 0.924,
+                z: 1.102,
+            },
+            Color {
+                x: 3.912,
+                y: 2.452,
+                z: 2.142,
+            },
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Arc<Self> {
+        Self::new(
+            Color {
+                x: 1.657,
+                y: 0.880,
+                z: 0.521,
+            },
+            Color {
+                x: 9.224,
+                y: 6.270,
+                z: 4.837,
+            },
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Arc<Self> {
+        Self::new(
+            Color {
+                x: 0.155,
+                y: 0.117,
+                z: 0.138,
+            },
+            Color {
+                x: 4.828,
+                y: 3.122,
+                z: 2.147,
+            },
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f64) -> Color {
+        Color {
+            x: fresnel_conductor(cos_theta, self.eta.x, self.k.x),
+            y: fresnel_conductor(cos_theta, self.eta.y, self.k.y),
+            z: fresnel_conductor(cos_theta, self.eta.z, self.k.z),
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // the roughness of anisotropic metals is aligned with the tangents of the surface
+        let frame = Onb::from_w_tangents(&hit.normal, &hit.tangent, &hit.bitangent);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        if self.ggx.is_smooth() {
+            let n = Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            };
+            let wi = reflect(&wo, &n);
+            return Some(Scatter {
+                attenuation: self.fresnel(wo.z),
+                scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&wi)),
+            });
+        }
+
+        let wm = self.ggx.sample_visible_normal(&wo, rng);
+        let wi = reflect(&wo, &wm);
+        // the microfacet reflected the ray below the macro surface -> absorbed
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        // with visible normal sampling the pdf cancels D and G1(wo) out of the estimator, so the
+        // sample weight boils down to F * G2 / G1
+        let weight = self.ggx.g2(&wo, &wi) / self.ggx.g1(&wo);
+
+        Some(Scatter {
+            attenuation: self.fresnel(wo.dot(&wm)) * weight,
+            scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&wi)),
+        })
+    }
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        // a perfect mirror can only be sampled
+        if self.ggx.is_smooth() {
+            return None;
+        }
+
+        let frame = Onb::from_w_tangents(&hit.normal, &hit.tangent, &hit.bitangent);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        let wi = frame.to_local(&dir.norm());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Some(BsdfEval {
+                value: Color::zero(),
+                pdf: 0.0,
+            });
+        }
+
+        let wm = (wo + wi).norm();
+        // the visible normal pdf D * G1 * (wo·wm) / wo.z times the jacobian 1 / (4 wo·wm) of the
+        // reflection, and the brdf F * D * G2 / (4 wo.z wi.z) times the cosine wi.z
+        let d = self.ggx.d(&wm);
+        Some(BsdfEval {
+            value: self.fresnel(wo.dot(&wm)) * (d * self.ggx.g2(&wo, &wi) / (4.0 * wo.z)),
+            pdf: d * self.ggx.g1(&wo) / (4.0 * wo.z),
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use core::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    material::Material,
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    texture::Texture,
+    vec::{Color, Onb, Vec3},
+};
+
+// cosine weighted direction in the local space of a surface (z-axis = normal), the same
+// distribution `Lambertian` samples in world space
+fn sample_cosine(rng: &mut dyn RngCore) -> Vec3 {
+    let n = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+    let w = n + Vec3::rand_unit_sphere_vec(rng);
+    if w.near_zero() {
+        n
+    } else {
+        w.norm()
+    }
+}
+
+/// Rough diffuse surface made of V-shaped lambertian microfacets (Oren & Nayar 1994, qualitative
+/// model). Compared to `Lambertian` it scatters more light back towards the light source and looks
+/// flatter, which is what clay, plaster, cloth and the lunar regolith look like. `sigma` is the
+/// standard deviation of the facet slopes in degrees, 0 is lambertian.
+#[derive(Debug, Clone)]
+pub struct OrenNayar<T: Texture> {
+    tex: T,
+    a: f64,
+    b: f64,
+}
+
+impl<T: Texture> OrenNayar<T> {
+    pub fn new(tex: T, sigma: f64) -> Arc<Self> {
+        let sigma2 = sigma.to_radians().powi(2);
+        Arc::from(Self {
+            tex,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        })
+    }
+
+    // brdf relative to a lambertian one for the local outgoing and incident directions
+    fn weight(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        // cos(φi - φo) from the projections onto the tangent plane
+        let sin_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // sin(α) * tan(β) with α = max(θi, θo) and β = min(θi, θo)
+        let (sin_alpha, tan_beta) = if wi.z.abs() > wo.z.abs() {
+            (sin_o, sin_i / wi.z.abs().max(1e-4))
+        } else {
+            (sin_i, sin_o / wo.z.abs().max(1e-4))
+        };
+
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+impl<T: Texture> Material for OrenNayar<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        let wi = sample_cosine(rng);
+
+        // cosine weighted sampling cancels the cosine term and 1/π of the brdf
+        let weight = self.weight(&wo, &wi);
+
+        Some(Scatter {
+            attenuation: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * weight,
+            scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&wi)),
+        })
+    }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        let wi = frame.to_local(&dir.norm());
+        if wi.z <= 0.0 {
+            return Some(BsdfEval {
+                value: Color::zero(),
+                pdf: 0.0,
+            });
+        }
+
+        let pdf = wi.z / PI;
+        Some(BsdfEval {
+            value: self.tex.filtered(hit.uv, &hit.p, hit.footprint) * (self.weight(&wo, &wi) * pdf),
+            pdf,
+        })
+    }
+}
+
+/// Thin diffuse sheet that scatters part of the light to the side it came from and part of it to
+/// the other side (leaves, paper, lampshades). Both sides look the same and there is no interior
+/// medium, so it's meant for single quads and triangles rather than closed objects.
+#[derive(Debug, Clone)]
+pub struct DiffuseTransmission<R: Texture, T: Texture> {
+    reflectance: R,
+    transmittance: T,
+}
+
+impl<R: Texture, T: Texture> DiffuseTransmission<R, T> {
+    pub fn new(reflectance: R, transmittance: T) -> Arc<Self> {
+        Arc::from(Self {
+            reflectance,
+            transmittance,
+        })
+    }
+}
+
+impl<R: Texture, T: Texture> Material for DiffuseTransmission<R, T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let reflectance = self.reflectance.filtered(hit.uv, &hit.p, hit.footprint);
+        let transmittance = self.transmittance.filtered(hit.uv, &hit.p, hit.footprint);
+
+        // pick the side proportional to the brightness of both lobes and divide by the probability
+        let r = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+        let t = (transmittance.x + transmittance.y + transmittance.z) / 3.0;
+        if r + t <= 0.0 {
+            return None;
+        }
+        let p_reflect = r / (r + t);
+
+        let frame = Onb::from_w(&hit.normal);
+        let mut wi = sample_cosine(rng);
+        let attenuation: Color = if rng.random::<f64>() < p_reflect {
+            reflectance / p_reflect
+        } else {
+            wi.z = -wi.z;
+            transmittance / (1.0 - p_reflect)
+        };
+
+        Some(Scatter {
+            attenuation,
+            scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&wi)),
+        })
+    }
+
+    fn eval(&self, _incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let reflectance = self.reflectance.filtered(hit.uv, &hit.p, hit.footprint);
+        let transmittance = self.transmittance.filtered(hit.uv, &hit.p, hit.footprint);
+        let r = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+        let t = (transmittance.x + transmittance.y + transmittance.z) / 3.0;
+        if r + t <= 0.0 {
+            return Some(BsdfEval {
+                value: Color::zero(),
+                pdf: 0.0,
+            });
+        }
+        let p_reflect = r / (r + t);
+
+        // a lambertian lobe on either side, sampled with the probability of its side
+        let cos_theta = hit.normal.dot(&dir.norm());
+        let (color, p_side) = if cos_theta > 0.0 {
+            (reflectance, p_reflect)
+        } else {
+            (transmittance, 1.0 - p_reflect)
+        };
+        let cos_theta = cos_theta.abs();
+        Some(BsdfEval {
+            value: color * (cos_theta / PI),
+            pdf: p_side * cos_theta / PI,
+        })
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    material::{
+        microfacet::{fresnel_dielectric, reflect, refract, Ggx},
+        Material,
+    },
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    texture::{SolidTex, Texture},
+    vec::{Color, Onb, Vec3},
+};
+
+/// Blends two materials by a mask: where the mask is 0 the surface is `a`, where it's 1 it is `b`.
+/// In between every scatter event picks one of them with the probability given by the mask.
+#[derive(Debug, Clone)]
+pub struct Mix<T: Texture> {
+    a: Arc<dyn Material>,
+    b: Arc<dyn Material>,
+    mask: T,
+}
+
+impl<T: Texture> Mix<T> {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, mask: T) -> Arc<Self> {
+        Arc::from(Self { a, b, mask })
+    }
+}
+
+impl Mix<SolidTex> {
+    // the same blend factor everywhere
+    pub fn uniform(a: Arc<dyn Material>, b: Arc<dyn Material>, factor: f64) -> Arc<Self> {
+        Self::new(a, b, SolidTex::splat(factor))
+    }
+}
+
+impl<T: Texture> Material for Mix<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        if rng.random::<f64>() < self.mask.scalar(hit.uv, &hit.p) {
+            self.b.scatter(incident_ray, hit, rng)
+        } else {
+            self.a.scatter(incident_ray, hit, rng)
+        }
+    }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let t = self.mask.scalar(hit.uv, &hit.p).clamp(0.0, 1.0);
+        let a = self.a.eval(incident_ray, hit, dir)?;
+        let b = self.b.eval(incident_ray, hit, dir)?;
+        Some(BsdfEval {
+            value: a.value * (1.0 - t) + b.value * t,
+            pdf: a.pdf * (1.0 - t) + b.pdf * t,
+        })
+    }
+
+    fn emit(&self, incident_ray: &Ray3, hit: &Hit) -> Color {
+        let t = self.mask.scalar(hit.uv, &hit.p).clamp(0.0, 1.0);
+        self.a.emit(incident_ray, hit) * (1.0 - t) + self.b.emit(incident_ray, hit) * t
+    }
+
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        let t = self.mask.scalar(hit.uv, &hit.p).clamp(0.0, 1.0);
+        self.a.emit_spectral(incident_ray, hit, lambda) * (1.0 - t)
+            + self.b.emit_spectral(incident_ray, hit, lambda) * t
+    }
+}
+
+// light that keeps bouncing between the base and the coating is terminated after this many round
+// trips. Only matters for very reflective bases, every trip loses energy at both interfaces.
+const MAX_COAT_BOUNCES: u32 = 16;
+
+/// A thin dielectric coating (varnish, lacquer, clearcoat) on top of any base material. Light is
+/// either reflected by the coating or refracted into it, travels through the (optionally
+/// absorbing) layer to the base, gets scattered by the base and then bounces between the base and
+/// the underside of the coating until it leaves the layer again.
+#[derive(Debug, Clone)]
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refractive_index: f64,
+    ggx: Ggx,
+    // optical depth of the layer at normal incidence per rgb channel
+    absorption: Color,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refractive_index: f64, roughness: f64) -> Arc<Self> {
+        Self::tinted(base, refractive_index, roughness, Color::white())
+    }
+
+    // coloured coating that lets `color` of white light through when it's crossed once at normal
+    // incidence. Light that crosses it at an angle or multiple times is tinted more strongly.
+    pub fn tinted(
+        base: Arc<dyn Material>,
+        refractive_index: f64,
+        roughness: f64,
+        color: Color,
+    ) -> Arc<Self> {
+        let depth = |c: f64| -c.max(1e-6).ln();
+        Arc::from(Self {
+            base,
+            refractive_index,
+            ggx: Ggx::new(roughness, roughness),
+            absorption: Color {
+                x: depth(color.x),
+                y: depth(color.y),
+                z: depth(color.z),
+            },
+        })
+    }
+
+    // attenuation along a straight path through the layer with the given direction (local space)
+    fn transmittance(&self, w: &Vec3) -> Color {
+        let distance = 1.0 / w.z.abs().max(1e-4);
+        Color {
+            x: (-self.absorption.x * distance).exp(),
+            y: (-self.absorption.y * distance).exp(),
+            z: (-self.absorption.z * distance).exp(),
+        }
+    }
+
+    // samples the interface of the coating seen from wo (pointing away from it, local space with
+    // the z-axis on the side of wo) and returns either the reflected or the transmitted direction
+    // together with the masking weight
+    fn sample_interface(
+        &self,
+        wo: &Vec3,
+        eta: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(Vec3, bool, f64)> {
+        let wm = if self.ggx.is_smooth() {
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            }
+        } else {
+            self.ggx.sample_visible_normal(wo, rng)
+        };
+
+        let reflected = rng.random::<f64>() < fresnel_dielectric(wo.dot(&wm), eta);
+        let wi = if reflected {
+            reflect(wo, &wm)
+        } else {
+            refract(wo, &wm, eta)?
+        };
+        // the microfacet scattered the ray to the wrong side of the interface
+        if (wi.z > 0.0) != reflected {
+            return None;
+        }
+
+        let weight = if self.ggx.is_smooth() {
+            1.0
+        } else {
+            self.ggx.g2(wo, &wi) / self.ggx.g1(wo)
+        };
+        Some((wi, reflected, weight))
+    }
+}
+
+// mirrors a direction at the interface plane, which turns the local space of the top of the
+// coating into the local space of its underside and back
+fn flip(w: &Vec3) -> Vec3 {
+    Vec3 {
+        x: w.x,
+        y: w.y,
+        z: -w.z,
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // rays inside of the object (transmissive bases) never see the coating
+        if !hit.front_face {
+            return self.base.scatter(incident_ray, hit, rng);
+        }
+
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let (mut w, reflected, weight) =
+            self.sample_interface(&wo, 1.0 / self.refractive_index, rng)?;
+        let mut attenuation = Color::splat(weight);
+        if reflected {
+            return Some(Scatter {
+                attenuation,
+                scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&w)),
+            });
+        }
+
+        // random walk inside of the layer, w is always pointing down towards the base here
+        for _ in 0..MAX_COAT_BOUNCES {
+            attenuation = &attenuation * &self.transmittance(&w);
+
+            let scatter =
+                self.base
+                    .scatter(&incident_ray.spawn(hit.p, frame.to_world(&w)), hit, rng)?;
+            attenuation = &attenuation * &scatter.attenuation;
+
+            let up = frame.to_local(&scatter.scattered_ray.dir.norm());
+            // the base transmitted the light, it leaves the layer through the bottom
+            if up.z <= 0.0 {
+                return Some(Scatter {
+                    attenuation,
+                    scattered_ray: scatter.scattered_ray,
+                });
+            }
+            attenuation = &attenuation * &self.transmittance(&up);
+
+            // hit the coating from below
+            let (wi, reflected, weight) =
+                self.sample_interface(&flip(&(up * -1.0)), self.refractive_index, rng)?;
+            attenuation = attenuation * weight;
+            if !reflected {
+                return Some(Scatter {
+                    attenuation,
+                    scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&flip(&wi))),
+                });
+            }
+            w = flip(&wi);
+        }
+
+        None
+    }
+
+    // emissive bases shine through the coating
+    fn emit(&self, incident_ray: &Ray3, hit: &Hit) -> Color {
+        self.base.emit(incident_ray, hit)
+    }
+
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        self.base.emit_spectral(incident_ray, hit, lambda)
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{
+    material::Material,
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    texture::Texture,
+    vec::{Color, Vec3},
+};
+
+// how far scattered rays are pushed off the geometric surface
+const RAY_OFFSET: f64 = 1e-4;
+
+// the hit with its shading normal replaced by a perturbed one (given for the outside of the
+// surface)
+fn shaded_hit(incident_ray: &Ray3, hit: &Hit, outward_normal: Vec3) -> Hit {
+    let mut shaded = hit.clone();
+    let normal = if hit.front_face {
+        outward_normal
+    } else {
+        outward_normal * -1.0
+    };
+    // a normal that faces away from the viewer can't be shaded sensibly, fall back to the geometry
+    if normal.dot(&incident_ray.dir) < 0.0 {
+        shaded.normal = normal;
+    }
+    shaded
+}
+
+// the perturbed normal can send rays to the other side of the actual geometry than the shading
+// normal says (e.g. reflections into the surface). Those would leak light, so they are terminated.
+fn crosses_geometry(shaded: &Hit, dir: &Vec3) -> bool {
+    dir.dot(&shaded.geo_normal) * dir.dot(&shaded.normal) < 0.0
+}
+
+// scatters off the base material with a perturbed shading normal while the geometric normal keeps
+// deciding which side of the surface rays are on
+fn scatter_shaded(
+    base: &dyn Material,
+    incident_ray: &Ray3,
+    hit: &Hit,
+    outward_normal: Vec3,
+    rng: &mut dyn RngCore,
+) -> Option<Scatter> {
+    let shaded = shaded_hit(incident_ray, hit, outward_normal);
+    let mut scatter = base.scatter(incident_ray, &shaded, rng)?;
+
+    let dir = scatter.scattered_ray.dir;
+    if crosses_geometry(&shaded, &dir) {
+        return None;
+    }
+
+    // offset the origin along the geometric normal to the side the ray leaves to, so it doesn't
+    // intersect the surface it starts on again
+    let offset = if dir.dot(&hit.geo_normal) > 0.0 {
+        RAY_OFFSET
+    } else {
+        -RAY_OFFSET
+    };
+    scatter.scattered_ray.origin = hit.p + hit.geo_normal * offset;
+    Some(scatter)
+}
+
+// evaluates the base material with the same perturbed normal `scatter_shaded` uses. Directions
+// that `scatter_shaded` terminates carry no light but keep their density, since the base material
+// still samples them.
+fn eval_shaded(
+    base: &dyn Material,
+    incident_ray: &Ray3,
+    hit: &Hit,
+    outward_normal: Vec3,
+    dir: &Vec3,
+) -> Option<BsdfEval> {
+    let shaded = shaded_hit(incident_ray, hit, outward_normal);
+    let mut eval = base.eval(incident_ray, &shaded, dir)?;
+    if crosses_geometry(&shaded, dir) {
+        eval.value = Color::zero();
+    }
+    Some(eval)
+}
+
+// normal of the outside of the surface that hit.tangent and hit.bitangent form a right-handed
+// frame with
+fn outward_normal(hit: &Hit) -> Vec3 {
+    if hit.front_face {
+        hit.geo_normal
+    } else {
+        hit.geo_normal * -1.0
+    }
+}
+
+/// Perturbs the shading normal of a base material with a tangent space normal map: red and green
+/// move the normal along the u and v directions of the surface and blue along the normal. Maps
+/// have to be read linearly (no srgb decoding) and follow the OpenGL convention (green = +v).
+#[derive(Debug, Clone)]
+pub struct NormalMapped<T: Texture> {
+    base: Arc<dyn Material>,
+    map: T,
+    // scales the tangential part of the mapped normals, 0 = flat, 1 = as stored in the map
+    strength: f64,
+}
+
+impl<T: Texture> NormalMapped<T> {
+    pub fn new(base: Arc<dyn Material>, map: T, strength: f64) -> Arc<Self> {
+        Arc::from(Self {
+            base,
+            map,
+            strength,
+        })
+    }
+}
+
+impl<T: Texture> NormalMapped<T> {
+    fn normal(&self, hit: &Hit) -> Vec3 {
+        let texel = self.map.filtered(hit.uv, &hit.p, hit.footprint);
+        (hit.tangent * ((2.0 * texel.x - 1.0) * self.strength)
+            + hit.bitangent * ((2.0 * texel.y - 1.0) * self.strength)
+            + outward_normal(hit) * (2.0 * texel.z - 1.0).max(1e-3))
+        .norm()
+    }
+}
+
+impl<T: Texture> Material for NormalMapped<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        scatter_shaded(self.base.as_ref(), incident_ray, hit, self.normal(hit), rng)
+    }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        eval_shaded(self.base.as_ref(), incident_ray, hit, self.normal(hit), dir)
+    }
+
+    fn emit(&self, incident_ray: &Ray3, hit: &Hit) -> Color {
+        self.base.emit(incident_ray, hit)
+    }
+
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        self.base.emit_spectral(incident_ray, hit, lambda)
+    }
+}
+
+/// Perturbs the shading normal of a base material with the slope of a height field read from a
+/// texture (`Texture::scalar`). `scale` is the height of a value of 1 relative to the size of the
+/// texture in uv space.
+#[derive(Debug, Clone)]
+pub struct BumpMapped<T: Texture> {
+    base: Arc<dyn Material>,
+    height: T,
+    scale: f64,
+}
+
+impl<T: Texture> BumpMapped<T> {
+    pub fn new(base: Arc<dyn Material>, height: T, scale: f64) -> Arc<Self> {
+        Arc::from(Self {
+            base,
+            height,
+            scale,
+        })
+    }
+}
+
+impl<T: Texture> BumpMapped<T> {
+    fn normal(&self, hit: &Hit) -> Vec3 {
+        // slope of the height field by forward differences. The point is moved along with the uv
+        // coordinates so that solid (point based) textures work as height fields as well.
+        const DELTA: f64 = 1e-3;
+        let (u, v) = hit.uv;
+        let h = self.height.scalar(hit.uv, &hit.p);
+        let du = (self
+            .height
+            .scalar((u + DELTA, v), &(hit.p + hit.tangent * DELTA))
+            - h)
+            / DELTA;
+        let dv = (self
+            .height
+            .scalar((u, v + DELTA), &(hit.p + hit.bitangent * DELTA))
+            - h)
+            / DELTA;
+
+        (outward_normal(hit) - hit.tangent * (du * self.scale) - hit.bitangent * (dv * self.scale))
+            .norm()
+    }
+}
+
+impl<T: Texture> Material for BumpMapped<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        scatter_shaded(self.base.as_ref(), incident_ray, hit, self.normal(hit), rng)
+    }
+
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        eval_shaded(self.base.as_ref(), incident_ray, hit, self.normal(hit), dir)
+    }
+
+    fn emit(&self, incident_ray: &Ray3, hit: &Hit) -> Color {
+        self.base.emit(incident_ray, hit)
+    }
+
+    fn emit_spectral(&self, incident_ray: &Ray3, hit: &Hit, lambda: f64) -> f64 {
+        self.base.emit_spectral(incident_ray, hit, lambda)
+    }
+}
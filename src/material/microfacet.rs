@@ -0,0 +1,164 @@
+use core::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use crate::vec::Vec3;
+
+// Trowbridge-Reitz (GGX) microfacet distribution. All directions are given in the local shading
+// space of the surface where the (macro) normal is the z-axis.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    // perceptual roughness in [0,1] is remapped to alpha = roughness² which is closer to linear
+    pub(crate) fn new(roughness_x: f64, roughness_y: f64) -> Self {
+        Self {
+            alpha_x: (roughness_x * roughness_x).max(1e-4),
+            alpha_y: (roughness_y * roughness_y).max(1e-4),
+        }
+    }
+
+    // below this alpha the distribution is so narrow that we treat the surface as a perfect
+    // mirror instead of sampling (nearly) degenerate microfacet normals
+    pub(crate) fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // Smith auxiliary function Λ(w)
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let a2 = (self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2);
+        ((1.0 + a2 / cos2).sqrt() - 1.0) / 2.0
+    }
+
+    // masking function G1(w)
+    pub(crate) fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height-correlated masking-shadowing function G2(wo, wi)
+    pub(crate) fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // normal distribution function D(wm)
+    pub(crate) fn d(&self, wm: &Vec3) -> f64 {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let t = (wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2) + wm.z * wm.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    // density of `sample_visible_normal` returning wm for wo
+    pub(crate) fn visible_normal_pdf(&self, wo: &Vec3, wm: &Vec3) -> f64 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(wm).max(0.0) * self.d(wm) / wo.z
+    }
+
+    // samples a microfacet normal proportional to its visible (projected) area as seen from wo.
+    // Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+    pub(crate) fn sample_visible_normal(&self, wo: &Vec3, rng: &mut dyn RngCore) -> Vec3 {
+        // stretch wo into the configuration of a hemisphere with alpha = 1
+        let vh = Vec3 {
+            x: self.alpha_x * wo.x,
+            y: self.alpha_y * wo.y,
+            z: wo.z,
+        }
+        .norm();
+
+        let len_sqr = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sqr > 0.0 {
+            Vec3 {
+                x: -vh.y,
+                y: vh.x,
+                z: 0.0,
+            } / len_sqr.sqrt()
+        } else {
+            Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+        };
+        let t2 = vh.cross(&t1);
+
+        // uniformly sample the projected area of the hemisphere...
+        let r = rng.random::<f64>().sqrt();
+        let phi = 2.0 * PI * rng.random::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        // ...and warp the lower half of the disk onto the visible part of the hemisphere
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        // unstretch back into the ellipsoid configuration
+        Vec3 {
+            x: self.alpha_x * nh.x,
+            y: self.alpha_y * nh.y,
+            z: nh.z.max(1e-6),
+        }
+        .norm()
+    }
+}
+
+// unpolarized fresnel reflectance of a conductor with complex index of refraction eta + ik
+pub(crate) fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2b2 + cos2;
+    let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// mirror reflection of wo about the microfacet normal wm (both pointing away from the surface)
+pub(crate) fn reflect(wo: &Vec3, wm: &Vec3) -> Vec3 {
+    *wm * (2.0 * wo.dot(wm)) - *wo
+}
+
+// unpolarized fresnel reflectance of a dielectric interface. `eta` is the ratio of the refractive
+// indices of the incident and the transmitted side (η_i / η_t)
+pub(crate) fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    // total internal reflection
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let rp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// refraction of wo through the microfacet normal wm. Returns None on total internal reflection
+pub(crate) fn refract(wo: &Vec3, wm: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = wo.dot(wm);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(*wo * -eta + *wm * (eta * cos_i - cos_t))
+}
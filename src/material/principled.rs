@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    material::{
+        microfacet::{fresnel_dielectric, reflect, refract, Ggx},
+        Material,
+    },
+    medium::Interior,
+    ray::{Hit, Ray3, Scatter},
+    texture::{SolidTex, Texture},
+    vec::{Color, Onb, Vec3},
+};
+
+/// Parameters of the principled material. Colours are read with `Texture::value`, all other
+/// parameters are scalars in [0,1] read with `Texture::scalar`, so every one of them can be a
+/// constant (`SolidTex`) or a texture map exported from a DCC tool.
+#[derive(Debug, Clone)]
+pub struct PrincipledParams {
+    pub base_color: Arc<dyn Texture>,
+    // 0 = dielectric, 1 = metal
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    // strength of the specular reflection of dielectrics. 0.5 is a reflectance of 4% at normal
+    // incidence (ior 1.5) and maps linearly to 0-8%
+    pub specular: Arc<dyn Texture>,
+    // velvet-like retro-reflection at grazing angles (cloth)
+    pub sheen: Arc<dyn Texture>,
+    // tints the sheen from white towards the base colour
+    pub sheen_tint: Arc<dyn Texture>,
+    // strength of an additional white, ior 1.5 coating on top of everything else (car paint)
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_roughness: Arc<dyn Texture>,
+    // fraction of the dielectric base that refracts light instead of diffusing it (glass)
+    pub transmission: Arc<dyn Texture>,
+}
+
+impl Default for PrincipledParams {
+    fn default() -> Self {
+        Self {
+            base_color: Arc::new(SolidTex::splat(0.8)),
+            metallic: Arc::new(SolidTex::splat(0.0)),
+            roughness: Arc::new(SolidTex::splat(0.5)),
+            specular: Arc::new(SolidTex::splat(0.5)),
+            sheen: Arc::new(SolidTex::splat(0.0)),
+            sheen_tint: Arc::new(SolidTex::splat(0.5)),
+            clearcoat: Arc::new(SolidTex::splat(0.0)),
+            clearcoat_roughness: Arc::new(SolidTex::splat(0.03)),
+            transmission: Arc::new(SolidTex::splat(0.0)),
+        }
+    }
+}
+
+/// Disney-style "uber" material combining a diffuse base with sheen, a dielectric specular
+/// layer, metal and transmission, plus a clearcoat on top.
+///
+/// The lobes are layered by picking one of them per scatter event with the probability of the
+/// energy that reaches it (fresnel reflectance of the layers above it or the blend weights), so
+/// the sample weights never exceed the reflectance of the chosen lobe and the material can't
+/// create energy for any combination of parameters.
+#[derive(Debug, Clone)]
+pub struct Principled {
+    params: PrincipledParams,
+    interior: Interior,
+}
+
+impl Principled {
+    pub fn new(params: PrincipledParams) -> Arc<Self> {
+        Arc::from(Self {
+            params,
+            interior: Interior::new(Color::zero()),
+        })
+    }
+
+    // the interior rays transmitted into the object carry on their medium stack, see
+    // `Dielectric::interior`
+    pub fn interior(&self) -> Interior {
+        self.interior
+    }
+}
+
+// refractive index of the clearcoat layer
+const CLEARCOAT_IOR: f64 = 1.5;
+
+const NORMAL: Vec3 = Vec3 {
+    x: 0.0,
+    y: 0.0,
+    z: 1.0,
+};
+
+fn schlick(f0: &Color, cos_theta: f64) -> Color {
+    let t = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    *f0 + (Color::white() - *f0) * t
+}
+
+// a smooth surface only has a single microfacet normal which is the macro normal
+fn sample_normal(ggx: &Ggx, wo: &Vec3, rng: &mut dyn RngCore) -> Vec3 {
+    if ggx.is_smooth() {
+        NORMAL
+    } else {
+        ggx.sample_visible_normal(wo, rng)
+    }
+}
+
+// with visible normal sampling the sample weight of a microfacet lobe is F * G2 / G1
+fn masking_weight(ggx: &Ggx, wo: &Vec3, wi: &Vec3) -> f64 {
+    if ggx.is_smooth() {
+        1.0
+    } else {
+        ggx.g2(wo, wi) / ggx.g1(wo)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let (uv, p) = (hit.uv, &hit.p);
+        let params = &self.params;
+
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let base_color = params.base_color.filtered(uv, p, hit.footprint);
+        let roughness = params.roughness.scalar(uv, p);
+        let ggx = Ggx::new(roughness, roughness);
+
+        // reflectance at normal incidence of the dielectric base -> refractive index
+        let f0 = (0.08 * params.specular.scalar(uv, p)).clamp(0.0, 0.99);
+        let ior = (1.0 + f0.sqrt()) / (1.0 - f0.sqrt());
+        // ratio of the refractive indices on the incident and the transmitted side
+        let eta = if hit.front_face { 1.0 / ior } else { ior };
+
+        let scatter = |wi: Vec3, attenuation: Color| {
+            Some(Scatter {
+                attenuation,
+                scattered_ray: incident_ray.spawn(hit.p, frame.to_world(&wi)),
+            })
+        };
+
+        // rays that were refracted into the object only see the inner side of the dielectric
+        // interface, all the other lobes live on the outside
+        if hit.front_face {
+            // clearcoat: reflect with the fresnel reflectance of the coating, everything else is
+            // transmitted to the layers below
+            let clearcoat = params.clearcoat.scalar(uv, p);
+            if clearcoat > 0.0
+                && rng.random::<f64>() < clearcoat * fresnel_dielectric(wo.z, 1.0 / CLEARCOAT_IOR)
+            {
+                let coat_roughness = params.clearcoat_roughness.scalar(uv, p);
+                let coat = Ggx::new(coat_roughness, coat_roughness);
+                let wi = reflect(&wo, &sample_normal(&coat, &wo, rng));
+                if wi.z <= 0.0 {
+                    return None;
+                }
+                return scatter(wi, Color::splat(masking_weight(&coat, &wo, &wi)));
+            }
+
+            // metal: tinted specular reflection only
+            if rng.random::<f64>() < params.metallic.scalar(uv, p) {
+                let wm = sample_normal(&ggx, &wo, rng);
+                let wi = reflect(&wo, &wm);
+                if wi.z <= 0.0 {
+                    return None;
+                }
+                return scatter(
+                    wi,
+                    schlick(&base_color, wo.dot(&wm)) * masking_weight(&ggx, &wo, &wi),
+                );
+            }
+        }
+
+        // dielectric specular: reflect proportional to the fresnel reflectance of the sampled
+        // microfacet, the rest enters the surface
+        let wm = sample_normal(&ggx, &wo, rng);
+        if rng.random::<f64>() < fresnel_dielectric(wo.dot(&wm), eta) {
+            let wi = reflect(&wo, &wm);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            return scatter(wi, Color::splat(masking_weight(&ggx, &wo, &wi)));
+        }
+
+        // transmission: refract through the microfacet, tinted by the base colour on the way in
+        if !hit.front_face || rng.random::<f64>() < params.transmission.scalar(uv, p) {
+            let wi = refract(&wo, &wm, eta)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            let tint = if hit.front_face {
+                base_color
+            } else {
+                Color::white()
+            };
+            // keep track of whether the ray is inside the object, like `Dielectric` does
+            let mut scattered_ray = incident_ray.spawn(hit.p, frame.to_world(&wi));
+            if hit.front_face {
+                scattered_ray.media.push(self.interior);
+            } else {
+                scattered_ray.media.remove(&self.interior);
+            }
+            return Some(Scatter {
+                attenuation: tint * masking_weight(&ggx, &wo, &wi),
+                scattered_ray,
+            });
+        }
+
+        // diffuse: cosine weighted hemisphere sampling cancels the lambertian brdf down to the base
+        // colour
+        let mut wi = NORMAL + Vec3::rand_unit_sphere_vec(rng);
+        if wi.near_zero() {
+            wi = NORMAL;
+        }
+        let wi = wi.norm();
+
+        // sheen (Burley 2012) is an additional lobe that grows towards grazing angles between wi
+        // and the half vector. It's kept energy conserving by blending the diffuse colour towards
+        // the sheen colour instead of adding it on top.
+        let sheen = params.sheen.scalar(uv, p);
+        let attenuation = if sheen > 0.0 {
+            // hue of the base colour at full brightness
+            let max = base_color.x.max(base_color.y).max(base_color.z);
+            let tint_color = if max > 0.0 {
+                base_color / max
+            } else {
+                Color::white()
+            };
+            let sheen_tint = params.sheen_tint.scalar(uv, p);
+            let sheen_color = Color::white() * (1.0 - sheen_tint) + tint_color * sheen_tint;
+
+            let h = (wo + wi).norm();
+            let amount = (sheen * (1.0 - wi.dot(&h).clamp(0.0, 1.0)).powi(5)).min(1.0);
+            base_color * (1.0 - amount) + sheen_color * amount
+        } else {
+            base_color
+        };
+
+        scatter(wi, attenuation)
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    material::{
+        microfacet::{fresnel_dielectric, reflect, refract, Ggx},
+        Material,
+    },
+    medium::Interior,
+    ray::{BsdfEval, Hit, Ray3, Scatter},
+    texture::Texture,
+    vec::{Color, Onb, Vec3},
+};
+
+/// Frosted glass: a dielectric interface made of GGX microfacets that both reflect and transmit
+/// light (Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces").
+/// Roughness is read from a texture so that etched patterns can be mixed with clear glass.
+#[derive(Debug, Clone)]
+pub struct RoughDielectric<T: Texture> {
+    refractive_index: f64,
+    roughness: T,
+    interior: Interior,
+}
+
+impl<T: Texture> RoughDielectric<T> {
+    pub fn new(refractive_index: f64, roughness: T) -> Arc<Self> {
+        Self::absorbing(refractive_index, roughness, Color::zero())
+    }
+
+    // coloured frosted glass: light travelling through the inside is attenuated per unit distance
+    // by exp(-absorption)
+    pub fn absorbing(refractive_index: f64, roughness: T, absorption: Color) -> Arc<Self> {
+        Arc::from(Self {
+            refractive_index,
+            roughness,
+            interior: Interior::new(absorption),
+        })
+    }
+
+    // coloured frosted glass specified by the colour that's left of white light after travelling
+    // `distance` units through it
+    pub fn tinted(refractive_index: f64, roughness: T, color: Color, distance: f64) -> Arc<Self> {
+        Arc::from(Self {
+            refractive_index,
+            roughness,
+            interior: Interior::from_transmittance(color, distance),
+        })
+    }
+
+    // the interior rays refracted into the object carry on their medium stack, see
+    // `Dielectric::interior`
+    pub fn interior(&self) -> Interior {
+        self.interior
+    }
+}
+
+impl<T: Texture> Material for RoughDielectric<T> {
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // ratio of the refractive indices on the incident and the transmitted side
+        let eta = if hit.front_face {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let roughness = self.roughness.scalar(hit.uv, &hit.p);
+        let ggx = Ggx::new(roughness, roughness);
+
+        // a smooth surface only has a single microfacet normal which is the macro normal
+        let wm = if ggx.is_smooth() {
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            }
+        } else {
+            ggx.sample_visible_normal(&wo, rng)
+        };
+
+        // pick reflection or transmission proportional to the fresnel reflectance of the sampled
+        // microfacet, so F cancels out of the sample weight
+        let fresnel = fresnel_dielectric(wo.dot(&wm), eta);
+        let reflected = rng.random::<f64>() < fresnel;
+        let wi = if reflected {
+            let wi = reflect(&wo, &wm);
+            // reflected into the surface
+            if wi.z <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(&wo, &wm, eta)?;
+            // transmitted back out of the surface
+            if wi.z >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let weight = if ggx.is_smooth() {
+            1.0
+        } else {
+            ggx.g2(&wo, &wi) / ggx.g1(&wo)
+        };
+
+        let mut scattered_ray = incident_ray.spawn(hit.p, frame.to_world(&wi));
+        // keep track of whether the ray is inside the glass, like `Dielectric` does
+        if !reflected {
+            if hit.front_face {
+                scattered_ray.media.push(self.interior);
+            } else {
+                scattered_ray.media.remove(&self.interior);
+            }
+        }
+
+        Some(Scatter {
+            attenuation: Color::splat(weight),
+            scattered_ray,
+        })
+    }
+    fn eval(&self, incident_ray: &Ray3, hit: &Hit, dir: &Vec3) -> Option<BsdfEval> {
+        let roughness = self.roughness.scalar(hit.uv, &hit.p);
+        let ggx = Ggx::new(roughness, roughness);
+        // smooth glass can only be sampled
+        if ggx.is_smooth() {
+            return None;
+        }
+
+        let eta = if hit.front_face {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+        let frame = Onb::from_w(&hit.normal);
+        let wo = frame.to_local(&(incident_ray.dir.norm() * -1.0));
+        let wi = frame.to_local(&dir.norm());
+        let zero = BsdfEval {
+            value: Color::zero(),
+            pdf: 0.0,
+        };
+
+        // microfacet normal that turns wo into wi, and the jacobian of that mapping
+        let reflected = wi.z > 0.0;
+        let h = if reflected { wo + wi } else { wo * eta + wi };
+        if wo.z <= 0.0 || wi.z == 0.0 || h.near_zero() {
+            return Some(zero);
+        }
+        let mut wm = h.norm();
+        if wm.z < 0.0 {
+            wm = wm * -1.0;
+        }
+        let (cos_o, cos_i) = (wo.dot(&wm), wi.dot(&wm));
+        if cos_o <= 0.0 || (cos_i < 0.0) == reflected {
+            return Some(zero);
+        }
+        let fresnel = fresnel_dielectric(cos_o, eta);
+        let (lobe, jacobian) = if reflected {
+            (fresnel, 1.0 / (4.0 * cos_o))
+        } else {
+            (1.0 - fresnel, -cos_i / (eta * cos_o + cos_i).powi(2))
+        };
+
+        // visible normal pdf times the probability of the lobe, the sample weight of `scatter`
+        // is G2 / G1 on top of that
+        let pdf = ggx.visible_normal_pdf(&wo, &wm) * lobe * jacobian;
+        Some(BsdfEval {
+            value: Color::splat(pdf * ggx.g2(&wo, &wi) / ggx.g1(&wo)),
+            pdf,
+        })
+    }
+}
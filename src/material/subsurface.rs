@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{
+    material::{Dielectric, Ior, Material},
+    medium::Interior,
+    phase::Phase,
+    ray::{Hit, Ray3, Scatter},
+    vec::Color,
+};
+
+/// Translucent material whose light enters the object, scatters around inside of it and leaves it
+/// somewhere else (wax, marble, skin, milk). The interface is a smooth dielectric and the interior
+/// is a homogeneous scattering medium that the camera random walks through, so the object has to
+/// be closed.
+///
+/// `albedo` is the colour of a single scattering event and `mean_free_path` the average distance
+/// light travels between two of them per channel (in scene units). Longer paths for red than for
+/// blue give the typical reddish glow of skin. Every scattering event inside counts as a bounce
+/// of the path, so dense media need a much higher `max_bounces` than surfaces.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    surface: Dielectric,
+}
+
+impl Subsurface {
+    pub fn new(refractive_index: f64, albedo: Color, mean_free_path: Color) -> Arc<Self> {
+        Self::with_phase(refractive_index, albedo, mean_free_path, Phase::Isotropic)
+    }
+
+    // most natural translucent materials scatter forward (e.g. skin has g ≈ 0.8)
+    pub fn with_phase(
+        refractive_index: f64,
+        albedo: Color,
+        mean_free_path: Color,
+        phase: Phase,
+    ) -> Arc<Self> {
+        let mut interior = Interior::from_albedo(albedo, mean_free_path);
+        interior.phase = phase;
+        Arc::from(Self {
+            surface: Dielectric {
+                ior: Ior::Constant(refractive_index),
+                interior,
+            },
+        })
+    }
+}
+
+impl Material for Subsurface {
+    // the dielectric interface pushes the scattering interior onto the media of refracted rays
+    fn scatter(&self, incident_ray: &Ray3, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        self.surface.scatter(incident_ray, hit, rng)
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    phase::Phase,
+    vec::{Color, Point, Vec3},
+};
+
+// maximum number of nested interiors we keep track of. Rays that enter more nested objects than
+// this simply stop tracking the innermost ones.
+const MAX_DEPTH: usize = 8;
+
+static NEXT_INTERIOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Medium filling the inside of a closed object (e.g. the glass of a dielectric) that absorbs light
+/// according to the Beer-Lambert law and optionally scatters it (wax, marble, skin).
+#[derive(Clone, Copy, Debug)]
+pub struct Interior {
+    // identifies the object so that a ray leaving it removes the right stack entry
+    id: usize,
+    pub absorption: Color,
+    pub scattering: Color,
+    pub phase: Phase,
+}
+
+impl Interior {
+    pub fn new(absorption: Color) -> Self {
+        Self::with_scattering(absorption, Color::zero())
+    }
+
+    pub fn with_scattering(absorption: Color, scattering: Color) -> Self {
+        Self {
+            id: NEXT_INTERIOR_ID.fetch_add(1, Ordering::Relaxed),
+            absorption,
+            scattering,
+            phase: Phase::Isotropic,
+        }
+    }
+
+    // scattering medium specified by the colour of a single scattering event (albedo) and the
+    // average distance light travels between two events (mean free path) per channel
+    pub fn from_albedo(albedo: Color, mean_free_path: Color) -> Self {
+        let extinction = |d: f64| 1.0 / d.max(1e-6);
+        let extinction = Color {
+            x: extinction(mean_free_path.x),
+            y: extinction(mean_free_path.y),
+            z: extinction(mean_free_path.z),
+        };
+        let scattering = &extinction * &albedo;
+        Self::with_scattering(extinction - scattering, scattering)
+    }
+
+    // absorption coefficient that leaves `color` of the light after travelling `distance` units
+    pub fn from_transmittance(color: Color, distance: f64) -> Self {
+        let coefficient = |c: f64| -c.max(1e-6).ln() / distance;
+        Self::new(Color {
+            x: coefficient(color.x),
+            y: coefficient(color.y),
+            z: coefficient(color.z),
+        })
+    }
+
+    pub fn is_absorbing(&self) -> bool {
+        self.absorption.x > 0.0 || self.absorption.y > 0.0 || self.absorption.z > 0.0
+    }
+
+    pub fn is_scattering(&self) -> bool {
+        self.scattering.x > 0.0 || self.scattering.y > 0.0 || self.scattering.z > 0.0
+    }
+
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    // fraction of light that survives a path of length `distance` through the medium
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let extinction = self.extinction();
+        Color {
+            x: (-extinction.x * distance).exp(),
+            y: (-extinction.y * distance).exp(),
+            z: (-extinction.z * distance).exp(),
+        }
+    }
+
+    // samples the distance to the next scattering event of a ray that travels `max_distance`
+    // through the medium before it hits a surface. The extinction differs per channel, so the
+    // distance is sampled from the extinction of a random channel and weighted by the average pdf
+    // over all channels (spectral MIS) which keeps the weights bounded.
+    pub fn free_flight(&self, max_distance: f64, rng: &mut dyn RngCore) -> FreeFlight {
+        let extinction = self.extinction();
+        let channel = match rng.random_range(0..3) {
+            0 => extinction.x,
+            1 => extinction.y,
+            _ => extinction.z,
+        };
+        let distance = if channel > 0.0 {
+            -(1.0 - rng.random::<f64>()).ln() / channel
+        } else {
+            f64::INFINITY
+        };
+
+        if distance < max_distance {
+            let transmittance = self.transmittance(distance);
+            let pdf = &extinction * &transmittance;
+            FreeFlight::Scatter {
+                distance,
+                weight: &self.scattering * &transmittance / ((pdf.x + pdf.y + pdf.z) / 3.0),
+            }
+        } else {
+            let transmittance = self.transmittance(max_distance);
+            let pdf = (transmittance.x + transmittance.y + transmittance.z) / 3.0;
+            FreeFlight::Pass {
+                weight: if pdf > 0.0 {
+                    transmittance / pdf
+                } else {
+                    Color::zero()
+                },
+            }
+        }
+    }
+}
+
+// two interiors are the same if they belong to the same object, regardless of their coefficients
+impl PartialEq for Interior {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Outcome of sampling the free flight of a ray through a scattering medium.
+pub enum FreeFlight {
+    // the ray scatters `distance` units along its direction
+    Scatter { distance: f64, weight: Color },
+    // the ray reaches the next surface
+    Pass { weight: Color },
+}
+
+/// Participating medium that fills all of the space outside of objects, e.g. haze or fog around
+/// the whole scene. Unlike a `ConstantMedium` it has no boundary, so it also applies to rays that
+/// escape to the background.
+///
+/// The density falls off exponentially with the height (y) above `base_height`, like the density
+/// of the earth's atmosphere. A `height_falloff` of 0 gives a homogeneous medium.
+#[derive(Clone, Copy, Debug)]
+pub struct Atmosphere {
+    // extinction coefficient at `base_height` (1 / mean free path)
+    pub density: f64,
+    // colour of a single scattering event
+    pub albedo: Color,
+    // rate of the exponential falloff of the density per unit of height
+    pub height_falloff: f64,
+    pub base_height: f64,
+    pub phase: Phase,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            albedo: Color::white(),
+            height_falloff: 0.0,
+            base_height: 0.0,
+            phase: Phase::Isotropic,
+        }
+    }
+}
+
+impl Atmosphere {
+    pub fn density_at(&self, p: &Point) -> f64 {
+        self.density * (-self.height_falloff * (p.y - self.base_height)).exp()
+    }
+
+    // optical depth from `origin` along the unit direction `dir` up to `distance`. The density
+    // along the ray is an exponential in the distance, so it can be integrated analytically.
+    fn optical_depth(&self, origin: &Point, dir: &Vec3, distance: f64) -> f64 {
+        let rate = self.height_falloff * dir.y;
+        let density = self.density_at(origin);
+        if rate.abs() < 1e-9 {
+            density * distance
+        } else {
+            density * (1.0 - (-rate * distance).exp()) / rate
+        }
+    }
+
+    // fraction of the light that passes the atmosphere from `origin` along the unit direction
+    // `dir` up to `max_distance` (which can be infinite) without being scattered or absorbed
+    pub fn transmittance(&self, origin: &Point, dir: &Vec3, max_distance: f64) -> f64 {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        (-self.optical_depth(origin, dir, max_distance)).exp()
+    }
+
+    // samples the distance to the next scattering event of a ray starting at `origin` in the unit
+    // direction `dir` that hits a surface after `max_distance` (which can be infinite). Distances
+    // are sampled proportional to the transmittance, so the weights reduce to the albedo.
+    pub fn free_flight(
+        &self,
+        origin: &Point,
+        dir: &Vec3,
+        max_distance: f64,
+        rng: &mut dyn RngCore,
+    ) -> FreeFlight {
+        let pass = FreeFlight::Pass {
+            weight: Color::white(),
+        };
+        if self.density <= 0.0 {
+            return pass;
+        }
+
+        // invert the optical depth for a sampled one. Rays that climb through an exponential
+        // atmosphere only ever see a finite optical depth and might not scatter at all.
+        let target = -(1.0 - rng.random::<f64>()).ln();
+        if target >= self.optical_depth(origin, dir, max_distance) {
+            return pass;
+        }
+        let rate = self.height_falloff * dir.y;
+        let density = self.density_at(origin);
+        let distance = if rate.abs() < 1e-9 {
+            target / density
+        } else {
+            -(1.0 - target * rate / density).ln() / rate
+        };
+
+        FreeFlight::Scatter {
+            distance,
+            weight: self.albedo,
+        }
+    }
+}
+
+/// Interiors a ray is currently travelling through, innermost last. Rays start out in vacuum
+/// (empty stack), push an interior when they refract into an object and remove it again when they
+/// refract out of it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediumStack {
+    entries: [Option<Interior>; MAX_DEPTH],
+    len: usize,
+}
+
+impl MediumStack {
+    pub fn push(&mut self, interior: Interior) {
+        if self.len < MAX_DEPTH {
+            self.entries[self.len] = Some(interior);
+            self.len += 1;
+        }
+    }
+
+    // removes the entry of the given interior. Objects don't have to be left in the order they
+    // were entered, e.g. when two objects overlap.
+    pub fn remove(&mut self, interior: &Interior) {
+        if let Some(idx) = self.entries[..self.len]
+            .iter()
+            .rposition(|entry| entry.is_some_and(|entry| entry.id == interior.id))
+        {
+            self.entries.copy_within(idx + 1..self.len, idx);
+            self.len -= 1;
+            self.entries[self.len] = None;
+        }
+    }
+
+    pub fn top(&self) -> Option<&Interior> {
+        self.entries[..self.len].last()?.as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
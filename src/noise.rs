@@ -0,0 +1,301 @@
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+use crate::vec::Point;
+
+// offset of the ridged multifractal. A signal of 1 - |noise| has its ridges at the zero crossings
+// of the noise.
+const RIDGE_OFFSET: f64 = 1.0;
+// how strongly an octave of the ridged multifractal is masked by the ridges of the previous one
+const RIDGE_GAIN: f64 = 2.0;
+
+/// Ken Perlin's improved gradient noise. The lattice is shuffled by a seed, so differently seeded
+/// generators give independent patterns.
+#[derive(Clone)]
+pub struct Perlin {
+    seed: u64,
+    // permutation of 0..256 repeated twice, which saves wrapping the indices of the lattice corners
+    perm: Box<[u8; 512]>,
+}
+
+impl std::fmt::Debug for Perlin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Perlin").field("seed", &self.seed).finish()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Parameters of fractal noise, which sums octaves of noise of increasing frequency and
+/// decreasing amplitude.
+#[derive(Debug, Clone, Copy)]
+pub struct Fractal {
+    pub octaves: u32,
+    // frequency multiplier between two octaves
+    pub lacunarity: f64,
+    // amplitude multiplier between two octaves
+    pub gain: f64,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Self {
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut SmallRng::seed_from_u64(seed));
+
+        let mut perm = Box::new([0; 512]);
+        for (i, p) in perm.iter_mut().enumerate() {
+            *p = table[i % 256];
+        }
+        Self { seed, perm }
+    }
+
+    /// Noise at `p` in about [-1,1]. It is 0 on the integer lattice and varies smoothly with a
+    /// feature size of about 1.
+    pub fn noise(&self, p: &Point) -> f64 {
+        let (xf, yf, zf) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (x, y, z) = (p.x - xf, p.y - yf, p.z - zf);
+        // the lattice repeats after 256 cells
+        let xi = (xf as i64).rem_euclid(256) as usize;
+        let yi = (yf as i64).rem_euclid(256) as usize;
+        let zi = (zf as i64).rem_euclid(256) as usize;
+
+        let perm = &self.perm;
+        let a = perm[xi] as usize + yi;
+        let aa = perm[a] as usize + zi;
+        let ab = perm[a + 1] as usize + zi;
+        let b = perm[xi + 1] as usize + yi;
+        let ba = perm[b] as usize + zi;
+        let bb = perm[b + 1] as usize + zi;
+
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(perm[aa], x, y, z), grad(perm[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(perm[ab], x, y - 1.0, z),
+                    grad(perm[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(perm[aa + 1], x, y, z - 1.0),
+                    grad(perm[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(perm[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    // sums the octaves of `octave(p)` with their amplitudes, normalized by the sum of the amplitudes
+    fn octaves(&self, p: &Point, fractal: &Fractal, mut octave: impl FnMut(&Point) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..fractal.octaves.max(1) {
+            sum += amplitude * octave(&(*p * frequency));
+            norm += amplitude;
+            frequency *= fractal.lacunarity;
+            amplitude *= fractal.gain;
+        }
+        sum / norm
+    }
+
+    /// Fractal brownian motion in about [-1,1]: the classic soft, cloudy fractal noise.
+    pub fn fbm(&self, p: &Point, fractal: &Fractal) -> f64 {
+        self.octaves(p, fractal, |p| self.noise(p))
+    }
+
+    /// Fractal sum of the absolute noise in [0,1]. The creases where the noise changes its sign
+    /// give it a billowy look (fire, marble veins).
+    pub fn turbulence(&self, p: &Point, fractal: &Fractal) -> f64 {
+        self.octaves(p, fractal, |p| self.noise(p).abs())
+    }
+
+    /// Musgrave's ridged multifractal in [0,1]. The inverted creases form sharp ridges and every
+    /// octave only adds detail close to the ridges of the previous one (mountain ranges, veins).
+    pub fn ridged(&self, p: &Point, fractal: &Fractal) -> f64 {
+        let mut weight = 1.0;
+        self.octaves(p, fractal, |p| {
+            let signal = (RIDGE_OFFSET - self.noise(p).abs()).powi(2) * weight;
+            weight = (signal * RIDGE_GAIN).clamp(0.0, 1.0);
+            signal
+        }) / (RIDGE_OFFSET * RIDGE_OFFSET)
+    }
+}
+
+// quintic smoothstep, whose vanishing second derivative avoids visible creases at cell borders
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// dot product of the offset to a lattice corner with one of the 12 gradients pointing to the edges
+// of a cube, picked by the hash of the corner
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..4 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Distance metric of cellular noise. It shapes the cells: round for Euclidean, diamond shaped
+/// for Manhattan and square for Chebyshev.
+#[derive(Debug, Clone, Copy)]
+pub enum Metric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    fn distance(&self, d: &[f64]) -> f64 {
+        match self {
+            Metric::Euclidean => d.iter().map(|c| c * c).sum::<f64>().sqrt(),
+            Metric::Manhattan => d.iter().map(|c| c.abs()).sum(),
+            Metric::Chebyshev => d.iter().fold(0.0, |m, c| m.max(c.abs())),
+        }
+    }
+}
+
+/// Distances to the closest and second closest feature point of cellular noise and the id of
+/// the cell of the closest one.
+#[derive(Debug, Clone, Copy)]
+pub struct Cells {
+    pub f1: f64,
+    pub f2: f64,
+    // random per cell and independent of the position of its feature point, e.g. to give every
+    // cell its own colour
+    pub id: u64,
+}
+
+/// Steven Worley's cellular noise. Every cell of the integer lattice contains one feature point
+/// at a random position, and the noise is the distance to the closest ones.
+#[derive(Debug, Clone, Copy)]
+pub struct Worley {
+    seed: u64,
+    // how far feature points move away from their cell centres, 0 gives a regular grid and 1
+    // fully random cells
+    jitter: f64,
+}
+
+impl Worley {
+    pub fn new(seed: u64, jitter: f64) -> Self {
+        Self {
+            seed,
+            jitter: jitter.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Cells of the noise in 3d with a cell size of 1.
+    pub fn cells(&self, p: &Point, metric: Metric) -> Cells {
+        let base = [p.x.floor(), p.y.floor(), p.z.floor()];
+        let mut cells = Cells {
+            f1: f64::INFINITY,
+            f2: f64::INFINITY,
+            id: 0,
+        };
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let cell = [
+                        base[0] + dx as f64,
+                        base[1] + dy as f64,
+                        base[2] + dz as f64,
+                    ];
+                    let hash = self.hash(&cell);
+                    let feature = self.feature_point(&cell, hash);
+                    let d =
+                        metric.distance(&[p.x - feature[0], p.y - feature[1], p.z - feature[2]]);
+                    cells.insert(d, splitmix(hash));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Cells of the noise in 2d, e.g. in uv space, with a cell size of 1. Unlike a slice through
+    /// the 3d noise all cells have about the same size.
+    pub fn cells_2d(&self, x: f64, y: f64, metric: Metric) -> Cells {
+        let base = [x.floor(), y.floor()];
+        let mut cells = Cells {
+            f1: f64::INFINITY,
+            f2: f64::INFINITY,
+            id: 0,
+        };
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let cell = [base[0] + dx as f64, base[1] + dy as f64];
+                let hash = self.hash(&cell);
+                let feature = self.feature_point(&cell, hash);
+                let d = metric.distance(&[x - feature[0], y - feature[1]]);
+                cells.insert(d, splitmix(hash));
+            }
+        }
+        cells
+    }
+
+    fn hash(&self, cell: &[f64]) -> u64 {
+        cell.iter()
+            .fold(self.seed, |h, &c| splitmix(h ^ (c as i64 as u64)))
+    }
+
+    // feature point of a cell with the given hash, every coordinate takes 16 bits of the hash
+    fn feature_point<const N: usize>(&self, cell: &[f64; N], hash: u64) -> [f64; N] {
+        let mut point = *cell;
+        for (i, c) in point.iter_mut().enumerate() {
+            let random = ((hash >> (16 * i)) & 0xffff) as f64 / 65536.0;
+            *c += 0.5 + self.jitter * (random - 0.5);
+        }
+        point
+    }
+}
+
+impl Cells {
+    fn insert(&mut self, distance: f64, id: u64) {
+        if distance < self.f1 {
+            self.f2 = self.f1;
+            self.f1 = distance;
+            self.id = id;
+        } else if distance < self.f2 {
+            self.f2 = distance;
+        }
+    }
+}
+
+// splitmix64 finalizer, a cheap hash with good avalanche behaviour
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
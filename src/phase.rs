@@ -0,0 +1,83 @@
+use core::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use crate::vec::{Onb, Vec3};
+
+/// Phase functions describe the directional distribution of light scattered by a particle of a
+/// participating medium. All of them are rotationally symmetric around the direction the light
+/// travelled in, so they only depend on the angle θ between the incoming and the scattered
+/// direction.
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Isotropic,
+    // Henyey-Greenstein with asymmetry g in (-1, 1): g > 0 scatters forward (fog, clouds, smoke),
+    // g < 0 backwards and g = 0 is isotropic
+    HenyeyGreenstein(f64),
+    // mix of a forward and a backward lobe, `weight` is the probability of the first one. Fits the
+    // measured phase functions of haze and dust a lot better than a single lobe.
+    DoubleHenyeyGreenstein { g1: f64, g2: f64, weight: f64 },
+    // scattering by particles much smaller than the wavelength (air molecules -> blue sky)
+    Rayleigh,
+}
+
+impl Phase {
+    // value of the (normalized) phase function for the cosine of the scattering angle
+    pub fn eval(&self, cos_theta: f64) -> f64 {
+        match self {
+            Phase::Isotropic => 1.0 / (4.0 * PI),
+            Phase::HenyeyGreenstein(g) => henyey_greenstein(cos_theta, *g),
+            Phase::DoubleHenyeyGreenstein { g1, g2, weight } => {
+                weight * henyey_greenstein(cos_theta, *g1)
+                    + (1.0 - weight) * henyey_greenstein(cos_theta, *g2)
+            }
+            Phase::Rayleigh => 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta),
+        }
+    }
+
+    // samples the direction light travelling along `dir` is scattered into, proportional to the
+    // phase function. The sample weight is therefore always 1.
+    pub fn sample(&self, dir: &Vec3, rng: &mut dyn RngCore) -> Vec3 {
+        let u = rng.random::<f64>();
+        let cos_theta = match self {
+            Phase::Isotropic => 1.0 - 2.0 * u,
+            Phase::HenyeyGreenstein(g) => sample_henyey_greenstein(u, *g),
+            Phase::DoubleHenyeyGreenstein { g1, g2, weight } => {
+                let g = if rng.random::<f64>() < *weight {
+                    g1
+                } else {
+                    g2
+                };
+                sample_henyey_greenstein(u, *g)
+            }
+            Phase::Rayleigh => {
+                // invert the cdf (μ³ + 3μ + 4) / 8 = u with cardano's formula
+                let q = 4.0 - 8.0 * u;
+                let d = (q * q / 4.0 + 1.0).sqrt();
+                (-q / 2.0 + d).cbrt() + (-q / 2.0 - d).cbrt()
+            }
+        }
+        .clamp(-1.0, 1.0);
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.random::<f64>();
+        Onb::from_w(&dir.norm()).to_world(&Vec3 {
+            x: sin_theta * phi.cos(),
+            y: sin_theta * phi.sin(),
+            z: cos_theta,
+        })
+    }
+}
+
+fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+}
+
+fn sample_henyey_greenstein(u: f64, g: f64) -> f64 {
+    if g.abs() < 1e-3 {
+        return 1.0 - 2.0 * u;
+    }
+    let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u);
+    (1.0 + g * g - s * s) / (2.0 * g)
+}
@@ -6,6 +6,7 @@ use crate::{
     aabb::AABB,
     interval::Interval,
     material::Material,
+    medium::MediumStack,
     vec::{Color, Point, Vec3},
 };
 
@@ -15,30 +16,91 @@ pub struct Ray3 {
     pub origin: Point,
     pub dir: Vec3,
     pub time: f64,
+    // wavelength in nm once the path has been split up by a dispersive material
+    pub wavelength: Option<f64>,
+    // interiors of the objects the ray is currently travelling through
+    pub media: MediumStack,
+    // area covered by the ray, used to filter textures
+    pub cone: RayCone,
+}
+
+/// Cone around a ray that approximates the area it covers, e.g. the pixel of a camera ray.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayCone {
+    // width of the cone at the origin of the ray
+    pub width: f64,
+    // growth of the width per unit of distance along the ray
+    pub spread: f64,
+}
+
+impl RayCone {
+    pub fn width_at(&self, distance: f64) -> f64 {
+        self.width + self.spread * distance
+    }
+
+    // cone of a ray continuing `distance` units further along the path. Every bounce is treated
+    // like a flat mirror, which underestimates the spread after curved or rough surfaces.
+    pub fn advance(&self, distance: f64) -> Self {
+        Self {
+            width: self.width_at(distance),
+            spread: self.spread,
+        }
+    }
 }
 
 impl Ray3 {
     pub fn with_time(origin: Point, dir: Vec3, time: f64) -> Self {
-        Self { origin, dir, time }
+        Self {
+            origin,
+            dir,
+            time,
+            wavelength: None,
+            media: MediumStack::default(),
+            cone: RayCone::default(),
+        }
     }
 
     pub fn without_time(origin: Point, dir: Vec3) -> Self {
+        Self::with_time(origin, dir, 0.0)
+    }
+
+    /// New ray starting at `origin` that inherits the time, wavelength and medium state of this
+    /// ray. Used for scattered rays as well as for rays transformed into object space.
+    pub fn spawn(&self, origin: Point, dir: Vec3) -> Self {
         Self {
             origin,
             dir,
-            time: 0.0,
+            ..self.clone()
         }
     }
 
     pub fn at(&self, t: f64) -> Point {
         &self.origin + &(&self.dir * t)
     }
+
+    // width of the area the ray's cone covers on a surface with the given normal at `t`, in world
+    // units. Grazing hits stretch the footprint, which is capped at 10 times the cone width.
+    pub fn footprint(&self, t: f64, normal: &Vec3) -> f64 {
+        let dir_len = self.dir.len();
+        let cos = (self.dir.dot(normal) / dir_len).abs().max(0.1);
+        self.cone.width_at(t * dir_len) / cos
+    }
 }
 
+#[derive(Clone)]
 pub struct Hit {
     pub p: Point,
+    // shading normal, facing against the incident ray. Starts out as the geometric normal and can
+    // be perturbed by normal/bump mapping materials.
     pub normal: Vec3,
+    // normal of the actual geometry, facing against the incident ray
+    pub geo_normal: Vec3,
+    // directions in which the u and v texture coordinates increase along the surface
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub uv: (f64, f64),
+    // width of the ray's footprint around the hit in uv units, used to filter textures
+    pub footprint: f64,
     pub front_face: bool,
     pub t: f64,
     pub mat: Arc<dyn Material>,
@@ -47,6 +109,17 @@ pub struct Hit {
 pub trait Hittable<R: Rng>: Debug + Send + Sync {
     fn hit(&self, ray: &Ray3, t_range: &mut Interval, rng: &mut R) -> Option<Hit>;
     fn bbox(&self) -> AABB;
+    // weight of a ray that travels through the media of the object within `t_range` without
+    // colliding. Media whose extinction differs per channel only sample their collisions in `hit`
+    // by the mean extinction, the difference of the channels is carried by this weight once the
+    // renderer knows how far the ray actually gets. Everything else passes rays unchanged.
+    fn pass_weight(&self, _ray: &Ray3, _t_range: &Interval, _rng: &mut R) -> Color {
+        Color::white()
+    }
+    // whether `pass_weight` can be anything but white, which lets containers skip it
+    fn has_pass_weight(&self) -> bool {
+        false
+    }
 }
 
 pub struct Hittables<R: Rng> {
@@ -110,9 +183,32 @@ impl<R: Rng> Hittable<R> for Hittables<R> {
     fn bbox(&self) -> AABB {
         self.bbox
     }
+
+    fn pass_weight(&self, ray: &Ray3, t_range: &Interval, rng: &mut R) -> Color {
+        self.objects
+            .iter()
+            .filter(|hittable| hittable.has_pass_weight())
+            .fold(Color::white(), |weight, hittable| {
+                &weight * &hittable.pass_weight(ray, t_range, rng)
+            })
+    }
+
+    fn has_pass_weight(&self) -> bool {
+        self.objects
+            .iter()
+            .any(|hittable| hittable.has_pass_weight())
+    }
 }
 
 pub struct Scatter {
     pub attenuation: Color,
     pub scattered_ray: Ray3,
 }
+
+// a material evaluated for a given scattered direction, see `Material::eval`
+pub struct BsdfEval {
+    // bsdf times the cosine between the direction and the normal (the phase function for media)
+    pub value: Color,
+    // density of `Material::scatter` sampling the direction, with respect to solid angle
+    pub pdf: f64,
+}
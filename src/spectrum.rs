@@ -0,0 +1,427 @@
+use std::sync::OnceLock;
+
+use rand::{Rng, RngCore};
+
+use crate::vec::{Color, Vec3};
+
+/// Visible range of wavelengths (in nm) that spectral paths are sampled from.
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+// piecewise gaussian with different widths left and right of the mean
+fn lobe(lambda: f64, mean: f64, sigma_left: f64, sigma_right: f64) -> f64 {
+    let sigma = if lambda < mean {
+        sigma_left
+    } else {
+        sigma_right
+    };
+    let t = (lambda - mean) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 2° colour matching functions (x̄, ȳ, z̄) at `lambda` nm, using the analytic multi-lobe
+/// fit from Wyman et al. 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching
+/// Functions".
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3 {
+        x: 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(lambda, 501.1, 20.4, 26.2),
+        y: 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1),
+        z: 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8),
+    }
+}
+
+/// CIE XYZ -> linear sRGB (D65 white point).
+pub fn xyz_to_srgb(xyz: &Vec3) -> Color {
+    Color {
+        x: 3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z,
+        y: -0.9692660 * xyz.x + 1.8760108 * xyz.y + 0.0415560 * xyz.z,
+        z: 0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z,
+    }
+}
+
+/// Uniformly samples a wavelength from the visible range. The pdf is `1 / (LAMBDA_MAX - LAMBDA_MIN)`.
+pub fn sample_wavelength(rng: &mut dyn RngCore) -> f64 {
+    rng.random_range(LAMBDA_MIN..LAMBDA_MAX)
+}
+
+/// Rgb weight of a path that carries a single uniformly sampled wavelength. The weights are
+/// normalized so that averaging them over many sampled wavelengths gives white, i.e. a path that
+/// turns monochromatic doesn't change the expected colour of the pixel.
+pub fn wavelength_weight(lambda: f64) -> Color {
+    static MEAN: OnceLock<Color> = OnceLock::new();
+    let mean = MEAN.get_or_init(|| {
+        let steps = 4700;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let sum = (0..steps).fold(Color::zero(), |sum, i| {
+            sum + xyz_to_srgb(&cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step))
+        });
+        sum / steps as f64
+    });
+
+    let rgb = xyz_to_srgb(&cie_xyz(lambda));
+    Color {
+        x: rgb.x / mean.x,
+        y: rgb.y / mean.y,
+        z: rgb.z / mean.z,
+    }
+}
+
+/// Colour space of the rendered (linear) pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    AcesCg,
+}
+
+impl ColorSpace {
+    pub fn from_xyz(&self, xyz: &Vec3) -> Color {
+        match self {
+            ColorSpace::Srgb => xyz_to_srgb(xyz),
+            // includes the bradford adaptation from the D65 to the ACES (~D60) white point
+            ColorSpace::AcesCg => Color {
+                x: 1.6410234 * xyz.x - 0.3248033 * xyz.y - 0.2364247 * xyz.z,
+                y: -0.6636629 * xyz.x + 1.6153316 * xyz.y + 0.0167563 * xyz.z,
+                z: 0.0117219 * xyz.x - 0.0082844 * xyz.y + 0.9883949 * xyz.z,
+            },
+        }
+    }
+
+    // converts the linear srgb colours of the rgb renderer into this colour space
+    pub fn from_srgb(&self, rgb: &Color) -> Color {
+        match self {
+            ColorSpace::Srgb => *rgb,
+            ColorSpace::AcesCg => Color {
+                x: 0.6130974 * rgb.x + 0.3395231 * rgb.y + 0.0473795 * rgb.z,
+                y: 0.0701937 * rgb.x + 0.9163539 * rgb.y + 0.0134524 * rgb.z,
+                z: 0.0206156 * rgb.x + 0.1095698 * rgb.y + 0.8698151 * rgb.z,
+            },
+        }
+    }
+}
+
+// integrates f over the visible range with 1nm steps
+fn integrate(f: impl Fn(f64) -> f64) -> f64 {
+    let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+    (0..steps).map(|i| f(LAMBDA_MIN + i as f64 + 0.5)).sum()
+}
+
+fn cie_y_integral() -> f64 {
+    static INTEGRAL: OnceLock<f64> = OnceLock::new();
+    *INTEGRAL.get_or_init(|| integrate(|lambda| cie_xyz(lambda).y))
+}
+
+/// CIE XYZ of a spectral power distribution, scaled such that a spectrum that's constant 1 has a
+/// luminance Y of 1.
+pub fn spectrum_to_xyz(spectrum: impl Fn(f64) -> f64) -> Vec3 {
+    let xyz = Vec3 {
+        x: integrate(|lambda| spectrum(lambda) * cie_xyz(lambda).x),
+        y: integrate(|lambda| spectrum(lambda) * cie_xyz(lambda).y),
+        z: integrate(|lambda| spectrum(lambda) * cie_xyz(lambda).z),
+    };
+    xyz / cie_y_integral()
+}
+
+/// Contribution of radiance `radiance` carried by a path with a uniformly sampled wavelength to
+/// the XYZ value of its pixel (the Monte Carlo estimate of `spectrum_to_xyz`).
+pub fn wavelength_to_xyz(lambda: f64, radiance: f64) -> Vec3 {
+    cie_xyz(lambda) * (radiance * (LAMBDA_MAX - LAMBDA_MIN) / cie_y_integral())
+}
+
+// CIE standard illuminants D50 and D65 from 380nm to 780nm in 10nm steps
+const D50: [f64; 41] = [
+    24.49, 29.87, 49.31, 56.51, 60.03, 57.82, 74.82, 87.25, 90.61, 91.37, 95.11, 91.96, 95.72,
+    96.61, 97.13, 102.10, 100.75, 102.32, 100.00, 97.74, 98.92, 93.50, 97.69, 99.27, 99.04, 95.72,
+    98.86, 95.67, 98.19, 103.00, 99.13, 87.38, 91.60, 92.89, 76.85, 86.51, 92.58, 78.23, 57.69,
+    82.92, 78.27,
+];
+const D65: [f64; 41] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86, 115.92, 108.81,
+    109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33, 95.79, 88.69, 90.01, 89.60,
+    87.70, 83.29, 83.70, 80.03, 80.21, 82.28, 78.28, 69.72, 71.61, 74.35, 61.60, 69.89, 75.09,
+    63.59, 46.42, 66.81, 63.38,
+];
+
+// linear interpolation into a table sampled every 10nm from 380nm on, clamped at both ends
+fn sample_table(table: &[f64; 41], lambda: f64) -> f64 {
+    let x = ((lambda - 380.0) / 10.0).clamp(0.0, 40.0);
+    let i = (x as usize).min(39);
+    let t = x - i as f64;
+    table[i] * (1.0 - t) + table[i + 1] * t
+}
+
+/// Spectral radiance of a black body at `kelvin` (Planck's law) with `lambda` in nm.
+pub fn blackbody(lambda: f64, kelvin: f64) -> f64 {
+    const H: f64 = 6.62607015e-34;
+    const C: f64 = 299792458.0;
+    const KB: f64 = 1.380649e-23;
+
+    let l = lambda * 1e-9;
+    2.0 * H * C * C / (l.powi(5) * ((H * C / (l * KB * kelvin)).exp() - 1.0))
+}
+
+/// CIE standard illuminants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Illuminant {
+    // incandescent / tungsten light
+    A,
+    // horizon daylight
+    D50,
+    // noon daylight and the white point of srgb
+    D65,
+    // equal energy
+    E,
+}
+
+/// Spectral power distributions that emitters can be specified with directly instead of an rgb
+/// colour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spectrum {
+    Blackbody(f64),
+    Illuminant(Illuminant),
+}
+
+impl Spectrum {
+    // unnormalized spectral power at `lambda` nm
+    pub fn sample(&self, lambda: f64) -> f64 {
+        match self {
+            Spectrum::Blackbody(kelvin) => blackbody(lambda, *kelvin),
+            Spectrum::Illuminant(Illuminant::A) => blackbody(lambda, 2856.0),
+            Spectrum::Illuminant(Illuminant::D50) => sample_table(&D50, lambda),
+            Spectrum::Illuminant(Illuminant::D65) => sample_table(&D65, lambda),
+            Spectrum::Illuminant(Illuminant::E) => 1.0,
+        }
+    }
+
+    pub fn to_xyz(&self) -> Vec3 {
+        spectrum_to_xyz(|lambda| self.sample(lambda))
+    }
+}
+
+// D65 scaled to a luminance of 1. Rgb colours are assumed to be lit by/relative to this white.
+fn d65_normalized(lambda: f64) -> f64 {
+    static SCALE: OnceLock<f64> = OnceLock::new();
+    let scale = SCALE.get_or_init(|| 1.0 / Spectrum::Illuminant(Illuminant::D65).to_xyz().y);
+    sample_table(&D65, lambda) * scale
+}
+
+// --- rgb -> spectrum upsampling ---
+//
+// Jakob & Hanika 2019, "A Low-Dimensional Function Space for Efficient Spectral Upsampling".
+// A reflectance spectrum is modelled as a sigmoid of a quadratic polynomial
+//
+// s(λ) = sigmoid(c0 * x² + c1 * x + c2) with x = (λ - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN)
+//
+// which is smooth and always bounded by [0,1]. The coefficients for a given rgb colour are found
+// with Newton's method such that the spectrum reproduces the colour under D65. Since that's too
+// slow to do per shading point, we fit a table over the rgb cube once and interpolate it.
+
+const TABLE_RES: usize = 16;
+const FIT_SAMPLES: usize = 64;
+
+fn sigmoid(t: f64) -> f64 {
+    if t.is_infinite() {
+        return if t > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + t / (2.0 * (1.0 + t * t).sqrt())
+}
+
+fn eval_coefficients(c: &[f64; 3], lambda: f64) -> f64 {
+    let x = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+    sigmoid((c[0] * x + c[1]) * x + c[2])
+}
+
+struct UpsamplingTable {
+    // nonlinear spacing of the brightness axis, denser around black and white
+    scale: [f64; TABLE_RES],
+    // indexed by [max channel][brightness][second channel][third channel]
+    coefficients: Vec<[f64; 3]>,
+}
+
+impl UpsamplingTable {
+    fn index(l: usize, z: usize, y: usize, x: usize) -> usize {
+        ((l * TABLE_RES + z) * TABLE_RES + y) * TABLE_RES + x
+    }
+
+    fn build() -> Self {
+        let smoothstep = |x: f64| x * x * (3.0 - 2.0 * x);
+        let mut scale = [0.0; TABLE_RES];
+        for (k, s) in scale.iter_mut().enumerate() {
+            *s = smoothstep(smoothstep(k as f64 / (TABLE_RES - 1) as f64));
+        }
+
+        // quadrature points and the rgb response of each of them under D65
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / FIT_SAMPLES as f64;
+        let samples: Vec<(f64, Color)> = (0..FIT_SAMPLES)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+                let weight = d65_normalized(lambda) * step / cie_y_integral();
+                (lambda, xyz_to_srgb(&(cie_xyz(lambda) * weight)))
+            })
+            .collect();
+        let to_rgb = |c: &[f64; 3]| {
+            samples
+                .iter()
+                .fold(Color::zero(), |rgb, (lambda, response)| {
+                    rgb + *response * eval_coefficients(c, *lambda)
+                })
+        };
+
+        let mut coefficients = vec![[0.0; 3]; 3 * TABLE_RES * TABLE_RES * TABLE_RES];
+        // neighbouring table entries have similar coefficients, so we walk the brightness axis
+        // outwards from a medium brightness and start every fit from the previous solution
+        let start = TABLE_RES / 5;
+        for l in 0..3 {
+            for y in 0..TABLE_RES {
+                for x in 0..TABLE_RES {
+                    let order = (start..TABLE_RES).chain((0..start).rev());
+                    let mut c = [0.0; 3];
+                    for z in order {
+                        if z == start - 1 {
+                            c = coefficients[Self::index(l, start, y, x)];
+                        }
+                        let b = scale[z];
+                        let mut rgb = [0.0; 3];
+                        rgb[l] = b;
+                        rgb[(l + 1) % 3] = x as f64 / (TABLE_RES - 1) as f64 * b;
+                        rgb[(l + 2) % 3] = y as f64 / (TABLE_RES - 1) as f64 * b;
+                        let target = Color {
+                            x: rgb[0],
+                            y: rgb[1],
+                            z: rgb[2],
+                        };
+                        c = fit(&target, c, &to_rgb);
+                        coefficients[Self::index(l, z, y, x)] = c;
+                    }
+                }
+            }
+        }
+
+        Self {
+            scale,
+            coefficients,
+        }
+    }
+
+    fn lookup(&self, rgb: &Color) -> [f64; 3] {
+        let rgb = [
+            rgb.x.clamp(0.0, 1.0),
+            rgb.y.clamp(0.0, 1.0),
+            rgb.z.clamp(0.0, 1.0),
+        ];
+        let l = if rgb[0] >= rgb[1] && rgb[0] >= rgb[2] {
+            0
+        } else if rgb[1] >= rgb[2] {
+            1
+        } else {
+            2
+        };
+        let b = rgb[l];
+        if b <= 0.0 {
+            return [0.0, 0.0, f64::NEG_INFINITY];
+        }
+
+        let res = (TABLE_RES - 1) as f64;
+        let x = rgb[(l + 1) % 3] / b * res;
+        let y = rgb[(l + 2) % 3] / b * res;
+        let xi = (x as usize).min(TABLE_RES - 2);
+        let yi = (y as usize).min(TABLE_RES - 2);
+        let zi = self
+            .scale
+            .partition_point(|s| *s <= b)
+            .saturating_sub(1)
+            .min(TABLE_RES - 2);
+
+        let dx = x - xi as f64;
+        let dy = y - yi as f64;
+        let dz = (b - self.scale[zi]) / (self.scale[zi + 1] - self.scale[zi]);
+
+        let mut c = [0.0; 3];
+        for (k, ck) in c.iter_mut().enumerate() {
+            let at = |z: usize, y: usize, x: usize| self.coefficients[Self::index(l, z, y, x)][k];
+            let lerp = |a: f64, b: f64, t: f64| a * (1.0 - t) + b * t;
+            let plane = |z: usize| {
+                lerp(
+                    lerp(at(z, yi, xi), at(z, yi, xi + 1), dx),
+                    lerp(at(z, yi + 1, xi), at(z, yi + 1, xi + 1), dx),
+                    dy,
+                )
+            };
+            *ck = lerp(plane(zi), plane(zi + 1), dz);
+        }
+        c
+    }
+}
+
+// newton iteration on the 3x3 system rgb(c) = target with a finite difference jacobian
+fn fit(target: &Color, mut c: [f64; 3], to_rgb: &impl Fn(&[f64; 3]) -> Color) -> [f64; 3] {
+    const EPS: f64 = 1e-4;
+
+    for _ in 0..20 {
+        let rgb = to_rgb(&c);
+        let r = rgb - *target;
+        if r.len_sqr() < 1e-12 {
+            break;
+        }
+
+        let mut jacobian = [Vec3::zero(); 3];
+        for (j, column) in jacobian.iter_mut().enumerate() {
+            let mut dc = c;
+            dc[j] += EPS;
+            *column = (to_rgb(&dc) - rgb) / EPS;
+        }
+
+        // cramer's rule
+        let det = jacobian[0].dot(&jacobian[1].cross(&jacobian[2]));
+        if det.abs() < 1e-15 {
+            break;
+        }
+        let step = [
+            r.dot(&jacobian[1].cross(&jacobian[2])) / det,
+            jacobian[0].dot(&r.cross(&jacobian[2])) / det,
+            jacobian[0].dot(&jacobian[1].cross(&r)) / det,
+        ];
+
+        // targets on the boundary of the rgb cube can only be reached asymptotically, so we limit
+        // the step size and the magnitude of the coefficients to keep the iteration stable
+        let len = step.iter().map(|s| s * s).sum::<f64>().sqrt();
+        let damping = if len > 50.0 { 50.0 / len } else { 1.0 };
+        for (ck, sk) in c.iter_mut().zip(step.iter()) {
+            *ck = (*ck - sk * damping).clamp(-1e4, 1e4);
+        }
+    }
+    c
+}
+
+fn upsampling_table() -> &'static UpsamplingTable {
+    static TABLE: OnceLock<UpsamplingTable> = OnceLock::new();
+    TABLE.get_or_init(UpsamplingTable::build)
+}
+
+/// Value at `lambda` of a smooth reflectance spectrum that looks like `rgb` (linear srgb). Colours
+/// brighter than 1 (e.g. path weights) are scaled down for the lookup and back up afterwards.
+pub fn rgb_reflectance(rgb: &Color, lambda: f64) -> f64 {
+    let max = rgb.x.max(rgb.y).max(rgb.z);
+    if max <= 0.0 {
+        return 0.0;
+    }
+    if max > 1.0 {
+        return max * eval_coefficients(&upsampling_table().lookup(&(*rgb / max)), lambda);
+    }
+    eval_coefficients(&upsampling_table().lookup(rgb), lambda)
+}
+
+/// Value at `lambda` of an emission spectrum that looks like `rgb` (linear srgb). The smooth
+/// reflectance is multiplied with D65 so that white light is the white point of srgb.
+pub fn rgb_illuminant(rgb: &Color, lambda: f64) -> f64 {
+    let max = rgb.x.max(rgb.y).max(rgb.z);
+    if max <= 0.0 {
+        return 0.0;
+    }
+    // scaling to a maximum of 0.5 keeps the fit away from the hard to reach boundary of the cube
+    let scale = 2.0 * max;
+    scale
+        * eval_coefficients(&upsampling_table().lookup(&(*rgb / scale)), lambda)
+        * d65_normalized(lambda)
+}
@@ -1,10 +1,69 @@
-use image::{ImageError, ImageReader, RgbImage};
+mod ops;
 
-use crate::vec::{Color, Point};
-use std::{fmt::Debug, path::Path};
+pub use ops::*;
+
+use image::{DynamicImage, ImageBuffer, ImageError, ImageReader, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::{
+    noise::{Fractal, Metric, Perlin, Worley},
+    spectrum::{self, Spectrum},
+    utils::{linear_to_srgb, srgb_to_linear},
+    vec::{Color, Point},
+};
+use std::{
+    fmt::Debug,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 pub trait Texture: Debug + Send + Sync {
     fn value(&self, uv: (f64, f64), p: &Point) -> Color;
+
+    // scalar material parameters (roughness, masks, ...) are read as the mean of the three channels
+    fn scalar(&self, uv: (f64, f64), p: &Point) -> f64 {
+        let c = self.value(uv, p);
+        (c.x + c.y + c.z) / 3.0
+    }
+
+    // spectral radiance at `lambda` nm when the texture is used as an emitter in spectral mode.
+    // Rgb textures are upsampled into a smooth spectrum that looks like their colour.
+    fn spectral_emission(&self, uv: (f64, f64), p: &Point, lambda: f64) -> f64 {
+        spectrum::rgb_illuminant(&self.value(uv, p), lambda)
+    }
+
+    // opacity used for cutouts, textures without an alpha channel are opaque
+    fn alpha(&self, _uv: (f64, f64), _p: &Point) -> f64 {
+        1.0
+    }
+
+    // value averaged over the footprint of a ray around the hit, `width` in uv units (see
+    // `Hit::footprint`). Textures that alias, like images, filter with it, the others ignore it.
+    fn filtered(&self, uv: (f64, f64), p: &Point, _width: f64) -> Color {
+        self.value(uv, p)
+    }
+}
+
+// shared textures, e.g. one texture that drives several inputs of a texture graph or a material
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        (**self).value(uv, p)
+    }
+
+    fn scalar(&self, uv: (f64, f64), p: &Point) -> f64 {
+        (**self).scalar(uv, p)
+    }
+
+    fn spectral_emission(&self, uv: (f64, f64), p: &Point, lambda: f64) -> f64 {
+        (**self).spectral_emission(uv, p, lambda)
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        (**self).alpha(uv, p)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        (**self).filtered(uv, p, width)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +76,12 @@ impl SolidTex {
         Self { albedo: color }
     }
 
+    pub fn splat(val: f64) -> Self {
+        Self {
+            albedo: Color::splat(val),
+        }
+    }
+
     pub fn red() -> Self {
         Self {
             albedo: Color::red(),
@@ -48,34 +113,96 @@ impl Texture for SolidTex {
     }
 }
 
+/// Emission given by a physical spectrum (blackbody temperature or standard illuminant) instead of
+/// an rgb colour. `intensity` is the luminance of the emitted light. The spectral renderer uses the
+/// exact spectrum, the rgb renderer its colour.
 #[derive(Debug, Clone)]
-pub struct CheckerTex {
-    even: SolidTex,
-    odd: SolidTex,
+pub struct SpectrumTex {
+    spectrum: Spectrum,
+    // scales the raw spectrum to the requested luminance
     scale: f64,
+    rgb: Color,
 }
 
-impl CheckerTex {
-    pub fn new(c1: Color, c2: Color, scale: f64) -> Self {
+impl SpectrumTex {
+    pub fn new(spectrum: Spectrum, intensity: f64) -> Self {
+        let xyz = spectrum.to_xyz();
+        let scale = intensity / xyz.y;
         Self {
-            even: SolidTex::new(c1),
-            odd: SolidTex::new(c2),
+            spectrum,
             scale,
+            rgb: spectrum::xyz_to_srgb(&(xyz * scale)),
+        }
+    }
+
+    pub fn blackbody(kelvin: f64, intensity: f64) -> Self {
+        Self::new(Spectrum::Blackbody(kelvin), intensity)
+    }
+
+    pub fn illuminant(illuminant: spectrum::Illuminant, intensity: f64) -> Self {
+        Self::new(Spectrum::Illuminant(illuminant), intensity)
+    }
+}
+
+impl Texture for SpectrumTex {
+    fn value(&self, _uv: (f64, f64), _p: &Point) -> Color {
+        // very warm or cold spectra lie outside of the srgb gamut
+        Color {
+            x: self.rgb.x.max(0.0),
+            y: self.rgb.y.max(0.0),
+            z: self.rgb.z.max(0.0),
         }
     }
+
+    fn spectral_emission(&self, _uv: (f64, f64), _p: &Point, lambda: f64) -> f64 {
+        self.spectrum.sample(lambda) * self.scale
+    }
 }
 
-impl Texture for CheckerTex {
-    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+#[derive(Debug, Clone)]
+pub struct CheckerTex<E: Texture = SolidTex, O: Texture = SolidTex> {
+    even: E,
+    odd: O,
+    scale: f64,
+}
+
+impl CheckerTex {
+    pub fn new(c1: Color, c2: Color, scale: f64) -> Self {
+        Self::from_textures(SolidTex::new(c1), SolidTex::new(c2), scale)
+    }
+}
+
+impl<E: Texture, O: Texture> CheckerTex<E, O> {
+    // checkerboard of two arbitrary textures
+    pub fn from_textures(even: E, odd: O, scale: f64) -> Self {
+        Self { even, odd, scale }
+    }
+}
+
+impl<E: Texture, O: Texture> CheckerTex<E, O> {
+    fn is_even(&self, uv: (f64, f64)) -> bool {
         let scaled_u = (uv.0 * self.scale).floor() as i32;
         let scaled_v = (uv.1 * self.scale).floor() as i32;
+        (scaled_u + scaled_v) % 2 == 0
+    }
+}
 
-        if (scaled_u + scaled_v) % 2 == 0 {
+impl<E: Texture, O: Texture> Texture for CheckerTex<E, O> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        if self.is_even(uv) {
             self.even.value(uv, p)
         } else {
             self.odd.value(uv, p)
         }
     }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        if self.is_even(uv) {
+            self.even.filtered(uv, p, width)
+        } else {
+            self.odd.filtered(uv, p, width)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -85,36 +212,57 @@ pub enum Orientation {
 }
 
 #[derive(Debug, Clone)]
-pub struct StripeTex {
-    even: SolidTex,
-    odd: SolidTex,
+pub struct StripeTex<E: Texture = SolidTex, O: Texture = SolidTex> {
+    even: E,
+    odd: O,
     scale: f64,
     orientation: Orientation,
 }
 
 impl StripeTex {
     pub fn new(c1: Color, c2: Color, scale: f64, orientation: Orientation) -> Self {
+        Self::from_textures(SolidTex::new(c1), SolidTex::new(c2), scale, orientation)
+    }
+}
+
+impl<E: Texture, O: Texture> StripeTex<E, O> {
+    // stripes of two arbitrary textures
+    pub fn from_textures(even: E, odd: O, scale: f64, orientation: Orientation) -> Self {
         Self {
-            even: SolidTex::new(c1),
-            odd: SolidTex::new(c2),
+            even,
+            odd,
             scale,
             orientation,
         }
     }
 }
 
-impl Texture for StripeTex {
-    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+impl<E: Texture, O: Texture> StripeTex<E, O> {
+    fn is_even(&self, uv: (f64, f64)) -> bool {
         let or = match self.orientation {
             Orientation::Vertical => (uv.0 * self.scale).floor() as i32,
             Orientation::Horizontal => (uv.1 * self.scale).floor() as i32,
         };
-        if or % 2 == 0 {
+        or % 2 == 0
+    }
+}
+
+impl<E: Texture, O: Texture> Texture for StripeTex<E, O> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        if self.is_even(uv) {
             self.even.value(uv, p)
         } else {
             self.odd.value(uv, p)
         }
     }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        if self.is_even(uv) {
+            self.even.filtered(uv, p, width)
+        } else {
+            self.odd.filtered(uv, p, width)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -140,32 +288,649 @@ impl Texture for UVTex {
     }
 }
 
+/// How an image texture is interpolated between its texels.
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // Catmull-Rom interpolation of 4x4 texels, sharper than bilinear when magnified
+    Bicubic,
+}
+
+/// How texture coordinates outside of [0,1] are mapped onto the image.
+#[derive(Debug, Clone, Copy)]
+pub enum Wrap {
+    Repeat,
+    // extend the texels at the border
+    Clamp,
+    // repeat the image mirrored, which hides the seams of images that don't tile
+    Mirror,
+}
+
+/// Transfer function the values of an image are stored with, which is undone to get linear
+/// texture values.
+#[derive(Debug, Clone, Copy)]
+pub enum Encoding {
+    // colour textures (albedo, emission) are usually stored sRGB encoded
+    Srgb,
+    // data textures (normal, roughness, masks) store their values as they are
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    // encoding of 8 and 16 bit images. Float images (HDR, EXR) are always linear.
+    pub encoding: Encoding,
+    pub filter: Filter,
+    pub wrap: Wrap,
+    // prefilter the image into a mip map pyramid, so that textures far away or at grazing angles
+    // are averaged over the footprint of a pixel instead of shimmering
+    pub mipmaps: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Srgb,
+            filter: Filter::Bilinear,
+            wrap: Wrap::Repeat,
+            mipmaps: true,
+        }
+    }
+}
+
+// a mip level in the bit depth of the source image. Integer levels keep their encoding and are
+// decoded per texel, which takes a quarter (8 bit) or half (16 bit) of the memory of floats.
+#[derive(Debug, Clone)]
+enum Level {
+    U8(RgbaImage),
+    U16(ImageBuffer<Rgba<u16>, Vec<u16>>),
+    F32(Rgba32FImage),
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageTex {
-    data: RgbImage,
+    // mip map levels, each half the size of the previous one. Just the image without mip maps.
+    levels: Vec<Level>,
+    options: ImageOptions,
 }
 
 impl Texture for ImageTex {
-    fn value(&self, uv: (f64, f64), _p: &Point) -> Color {
-        let width = self.data.width();
-        let height = self.data.height();
-
-        let u = uv.0;
-        let v = 1.0 - uv.1;
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
 
-        let i = (u * (width - 1) as f64) as u32;
-        let j = (v * (height - 1) as f64) as u32;
+    fn filtered(&self, uv: (f64, f64), _p: &Point, width: f64) -> Color {
+        let texel = self.lookup(uv, width);
+        Color {
+            x: texel[0],
+            y: texel[1],
+            z: texel[2],
+        }
+    }
 
-        self.data.get_pixel(i, j).into()
+    fn alpha(&self, uv: (f64, f64), _p: &Point) -> f64 {
+        self.lookup(uv, 0.0)[3]
     }
 }
 
 impl ImageTex {
     pub fn new(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::with_options(path, ImageOptions::default())
+    }
+
+    pub fn with_options(path: impl AsRef<Path>, options: ImageOptions) -> Result<Self, ImageError> {
         let img = ImageReader::open(path)
             .map_err(|err| ImageError::IoError(err))?
-            .decode()?
-            .into_rgb8();
-        Ok(Self { data: img })
+            .decode()?;
+        Ok(Self::from_image(img, options))
+    }
+
+    // 8 and 16 bit images are decoded according to the encoding of the options, float images are
+    // taken as linear
+    pub fn from_image(img: impl Into<DynamicImage>, options: ImageOptions) -> Self {
+        let first = Level::from_image(img.into());
+        let encoding = options.encoding;
+        let mut levels = Vec::new();
+        if options.mipmaps && (first.width() > 1 || first.height() > 1) {
+            // every level is averaged from the unrounded values of the previous one, so that the
+            // rounding errors of integer levels don't add up
+            let mut linear = downsample(first.width(), first.height(), |x, y| {
+                first.texel(x, y, encoding)
+            });
+            loop {
+                levels.push(first.encode(&linear, encoding));
+                if linear.width() == 1 && linear.height() == 1 {
+                    break;
+                }
+                linear = downsample(linear.width(), linear.height(), |x, y| {
+                    linear.get_pixel(x, y).0.map(|c| c as f64)
+                });
+            }
+        }
+        levels.insert(0, first);
+        Self { levels, options }
+    }
+
+    // filtered linear rgba value over a footprint of `width` in uv units. The footprint selects
+    // the mip level whose texels are about as large, neighbouring levels are blended (trilinear
+    // filtering).
+    fn lookup(&self, uv: (f64, f64), width: f64) -> [f64; 4] {
+        let size = self.levels[0].width().max(self.levels[0].height()) as f64;
+        let max_lod = (self.levels.len() - 1) as f64;
+        let lod = if width > 0.0 {
+            (width * size).log2().clamp(0.0, max_lod)
+        } else {
+            0.0
+        };
+
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+        let texel = self.sample_level(level, uv);
+        if t <= 0.0 {
+            return texel;
+        }
+        let next = self.sample_level(level + 1, uv);
+        std::array::from_fn(|c| texel[c] * (1.0 - t) + next[c] * t)
+    }
+
+    fn sample_level(&self, level: usize, uv: (f64, f64)) -> [f64; 4] {
+        let img = &self.levels[level];
+        // continuous texel coordinates with the texel centres at .5 and v pointing up
+        let x = uv.0 * img.width() as f64;
+        let y = (1.0 - uv.1) * img.height() as f64;
+
+        match self.options.filter {
+            Filter::Nearest => self.texel(level, x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let weights = [
+                    (0, 0, (1.0 - fx) * (1.0 - fy)),
+                    (1, 0, fx * (1.0 - fy)),
+                    (0, 1, (1.0 - fx) * fy),
+                    (1, 1, fx * fy),
+                ];
+                let mut sum = [0.0; 4];
+                for (dx, dy, w) in weights {
+                    let texel = self.texel(level, x0 + dx, y0 + dy);
+                    for c in 0..4 {
+                        sum[c] += texel[c] * w;
+                    }
+                }
+                sum
+            }
+            Filter::Bicubic => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let wx = catmull_rom(x - x0);
+                let wy = catmull_rom(y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let mut sum = [0.0; 4];
+                let mut min = [f64::INFINITY; 4];
+                let mut max = [f64::NEG_INFINITY; 4];
+                for (j, wy) in wy.iter().enumerate() {
+                    for (i, wx) in wx.iter().enumerate() {
+                        let texel = self.texel(level, x0 + i as i64 - 1, y0 + j as i64 - 1);
+                        for c in 0..4 {
+                            sum[c] += texel[c] * wx * wy;
+                            min[c] = min[c].min(texel[c]);
+                            max[c] = max[c].max(texel[c]);
+                        }
+                    }
+                }
+                // the negative lobes of the filter overshoot at sharp edges, which would give
+                // halos and negative values
+                std::array::from_fn(|c| sum[c].clamp(min[c], max[c]))
+            }
+        }
+    }
+
+    // linear texel of a mip level with the coordinates wrapped onto the image
+    fn texel(&self, level: usize, x: i64, y: i64) -> [f64; 4] {
+        let img = &self.levels[level];
+        let x = self.options.wrap.apply(x, img.width());
+        let y = self.options.wrap.apply(y, img.height());
+        img.texel(x, y, self.options.encoding)
+    }
+}
+
+impl Level {
+    fn from_image(img: DynamicImage) -> Self {
+        match img {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_) => Level::U8(img.into_rgba8()),
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => Level::U16(img.into_rgba16()),
+            _ => Level::F32(img.into_rgba32f()),
+        }
+    }
+
+    fn width(&self) -> u32 {
+        match self {
+            Level::U8(img) => img.width(),
+            Level::U16(img) => img.width(),
+            Level::F32(img) => img.width(),
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            Level::U8(img) => img.height(),
+            Level::U16(img) => img.height(),
+            Level::F32(img) => img.height(),
+        }
+    }
+
+    // linear rgba value of a texel. The alpha channel is linear in any case.
+    fn texel(&self, x: u32, y: u32, encoding: Encoding) -> [f64; 4] {
+        let srgb = matches!(encoding, Encoding::Srgb);
+        match self {
+            Level::U8(img) => {
+                let px = img.get_pixel(x, y).0;
+                std::array::from_fn(|c| {
+                    if srgb && c < 3 {
+                        srgb_table()[px[c] as usize] as f64
+                    } else {
+                        px[c] as f64 / 255.0
+                    }
+                })
+            }
+            Level::U16(img) => {
+                let px = img.get_pixel(x, y).0;
+                std::array::from_fn(|c| {
+                    let val = px[c] as f64 / 65535.0;
+                    if srgb && c < 3 {
+                        srgb_to_linear(val)
+                    } else {
+                        val
+                    }
+                })
+            }
+            Level::F32(img) => img.get_pixel(x, y).0.map(|c| c as f64),
+        }
+    }
+
+    // a level in the same bit depth (and encoding) as this one from linear values
+    fn encode(&self, linear: &Rgba32FImage, encoding: Encoding) -> Self {
+        // linear rgba value -> integer of an image with the given maximum
+        let quantize = |px: &Rgba<f32>, max: f64| -> [f64; 4] {
+            std::array::from_fn(|c| {
+                let val = (px.0[c] as f64).clamp(0.0, 1.0);
+                let val = if matches!(encoding, Encoding::Srgb) && c < 3 {
+                    linear_to_srgb(val)
+                } else {
+                    val
+                };
+                (val * max).round()
+            })
+        };
+
+        let (w, h) = linear.dimensions();
+        match self {
+            Level::U8(_) => Level::U8(RgbaImage::from_fn(w, h, |x, y| {
+                Rgba(quantize(linear.get_pixel(x, y), 255.0).map(|c| c as u8))
+            })),
+            Level::U16(_) => Level::U16(ImageBuffer::from_fn(w, h, |x, y| {
+                Rgba(quantize(linear.get_pixel(x, y), 65535.0).map(|c| c as u16))
+            })),
+            Level::F32(_) => Level::F32(linear.clone()),
+        }
+    }
+}
+
+// next mip level of a `width` x `height` image: every texel is the average of (up to) 2x2 linear
+// texels of the previous level
+fn downsample(width: u32, height: u32, texel: impl Fn(u32, u32) -> [f64; 4]) -> Rgba32FImage {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    Rgba32FImage::from_fn(w, h, |x, y| {
+        let mut sum = [0.0; 4];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let px = texel((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1));
+            for (s, c) in sum.iter_mut().zip(px) {
+                *s += c;
+            }
+        }
+        Rgba(sum.map(|c| (c / 4.0) as f32))
+    })
+}
+
+// linear values of all 8 bit sRGB encoded values
+fn srgb_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f64 / 255.0) as f32))
+}
+
+// linear rgba values of an image. 8 and 16 bit images are decoded according to `encoding`, float
+// images are taken as linear.
+pub(crate) fn to_linear(img: DynamicImage, encoding: Encoding) -> Rgba32FImage {
+    let is_float = matches!(
+        img,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    let mut img = img.into_rgba32f();
+    if !is_float && matches!(encoding, Encoding::Srgb) {
+        // the alpha channel is linear in any case
+        for px in img.pixels_mut() {
+            for c in &mut px.0[..3] {
+                *c = srgb_to_linear(*c as f64) as f32;
+            }
+        }
+    }
+    img
+}
+
+impl Wrap {
+    fn apply(&self, i: i64, size: u32) -> u32 {
+        let n = size as i64;
+        (match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        }) as u32
+    }
+}
+
+// Catmull-Rom weights of the 4 texels around a sample at offset t in [0,1) from the second one
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+/// Domain procedural textures are evaluated in.
+#[derive(Debug, Clone, Copy)]
+pub enum NoiseSpace {
+    // the 3d hit point, so the pattern runs through objects like a solid block of material
+    Point,
+    // the surface parametrization, with the pattern lying in the z = 0 plane
+    Uv,
+}
+
+/// Placement and detail of the noise of procedural textures.
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseOptions {
+    // frequency of the noise, i.e. the number of noise features per unit
+    pub scale: f64,
+    pub space: NoiseSpace,
+    pub fractal: Fractal,
+    // differently seeded textures give independent patterns
+    pub seed: u64,
+}
+
+impl Default for NoiseOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            space: NoiseSpace::Point,
+            fractal: Fractal::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl NoiseOptions {
+    fn point(&self, uv: (f64, f64), p: &Point) -> Point {
+        match self.space {
+            NoiseSpace::Point => *p * self.scale,
+            NoiseSpace::Uv => Point {
+                x: uv.0 * self.scale,
+                y: uv.1 * self.scale,
+                z: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NoisePattern {
+    // a single octave of gradient noise
+    Perlin,
+    Fbm,
+    Turbulence,
+    Ridged,
+}
+
+/// Grey scale noise in [0,1], e.g. as a roughness or bump map or a mask.
+#[derive(Debug, Clone)]
+pub struct NoiseTex {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    options: NoiseOptions,
+}
+
+impl NoiseTex {
+    pub fn new(pattern: NoisePattern, options: NoiseOptions) -> Self {
+        Self {
+            perlin: Perlin::new(options.seed),
+            pattern,
+            options,
+        }
+    }
+}
+
+impl Texture for NoiseTex {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let p = self.options.point(uv, p);
+        let fractal = &self.options.fractal;
+        let value = match self.pattern {
+            NoisePattern::Perlin => 0.5 * (1.0 + self.perlin.noise(&p)),
+            NoisePattern::Fbm => 0.5 * (1.0 + self.perlin.fbm(&p, fractal)),
+            NoisePattern::Turbulence => self.perlin.turbulence(&p, fractal),
+            NoisePattern::Ridged => self.perlin.ridged(&p, fractal),
+        };
+        Color::splat(value.clamp(0.0, 1.0))
+    }
+}
+
+/// Marble: bands of `vein` colour in the `base` along the x axis of the noise space that are
+/// distorted by turbulence.
+#[derive(Debug, Clone)]
+pub struct MarbleTex {
+    perlin: Perlin,
+    base: Color,
+    vein: Color,
+    // how far the turbulence displaces the bands
+    distortion: f64,
+    options: NoiseOptions,
+}
+
+impl MarbleTex {
+    pub fn new(base: Color, vein: Color, distortion: f64, options: NoiseOptions) -> Self {
+        Self {
+            perlin: Perlin::new(options.seed),
+            base,
+            vein,
+            distortion,
+            options,
+        }
+    }
+}
+
+impl Texture for MarbleTex {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let p = self.options.point(uv, p);
+        let phase = p.x + self.distortion * self.perlin.turbulence(&p, &self.options.fractal);
+        // sharpen the bands into thin veins
+        let t = (0.5 * (1.0 + phase.sin())).powi(3);
+        self.base * (1.0 - t) + self.vein * t
+    }
+}
+
+/// Wood: growth rings around the y axis of the noise space alternating between `light` and
+/// `dark`, wobbled by fbm.
+#[derive(Debug, Clone)]
+pub struct WoodTex {
+    perlin: Perlin,
+    light: Color,
+    dark: Color,
+    // number of rings per unit of distance from the axis (in noise space)
+    rings: f64,
+    options: NoiseOptions,
+}
+
+impl WoodTex {
+    pub fn new(light: Color, dark: Color, rings: f64, options: NoiseOptions) -> Self {
+        Self {
+            perlin: Perlin::new(options.seed),
+            light,
+            dark,
+            rings,
+            options,
+        }
+    }
+}
+
+impl Texture for WoodTex {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let p = self.options.point(uv, p);
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = radius * self.rings + 0.5 * self.perlin.fbm(&p, &self.options.fractal);
+        // late wood at the end of every ring is darker and sharper than the early wood
+        let t = rings.rem_euclid(1.0).powi(4);
+        self.light * (1.0 - t) + self.dark * t
+    }
+}
+
+/// Clouds: fbm thresholded into patches of `cloud` colour on a `sky` colour. `coverage` in [0,1]
+/// is roughly the fraction of the sky covered by clouds.
+#[derive(Debug, Clone)]
+pub struct CloudTex {
+    perlin: Perlin,
+    sky: Color,
+    cloud: Color,
+    coverage: f64,
+    options: NoiseOptions,
+}
+
+impl CloudTex {
+    pub fn new(sky: Color, cloud: Color, coverage: f64, options: NoiseOptions) -> Self {
+        Self {
+            perlin: Perlin::new(options.seed),
+            sky,
+            cloud,
+            coverage: coverage.clamp(0.0, 1.0),
+            options,
+        }
+    }
+}
+
+impl Texture for CloudTex {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let p = self.options.point(uv, p);
+        let density = 0.5 * (1.0 + self.perlin.fbm(&p, &self.options.fractal));
+        // fbm rarely leaves [0.25, 0.75], so the threshold is spread over that range and the
+        // edges of the clouds fade out over a small band above it
+        let threshold = 0.75 - 0.5 * self.coverage;
+        let t = ((density - threshold) / 0.15).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        self.sky * (1.0 - t) + self.cloud * t
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WorleyFeature {
+    // distance to the closest feature point: round spots that are dark at their centres
+    F1,
+    F2,
+    // distance between the two closest feature points, which is 0 on the borders of the cells
+    // (cracks, mortar between tiles or stones)
+    F2MinusF1,
+    // a random colour per cell
+    CellColor,
+}
+
+/// Placement and shape of the cells of cellular textures.
+#[derive(Debug, Clone)]
+pub struct WorleyOptions {
+    // number of cells per unit
+    pub scale: f64,
+    pub space: NoiseSpace,
+    pub metric: Metric,
+    // randomness of the cells in [0,1], 0 gives a regular grid of equal cells
+    pub jitter: f64,
+    // the cell colours are picked from these colours, or fully random if it's empty
+    pub palette: Vec<Color>,
+    pub seed: u64,
+}
+
+impl Default for WorleyOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            space: NoiseSpace::Point,
+            metric: Metric::Euclidean,
+            jitter: 1.0,
+            palette: Vec::new(),
+            seed: 0,
+        }
+    }
+}
+
+/// Cellular (Worley) noise. The distance features are grey scale, with distances measured in cells
+/// and clamped to [0,1]. In uv space the cells are laid out in 2d, so they all have about the same
+/// size on the surface.
+#[derive(Debug, Clone)]
+pub struct WorleyTex {
+    worley: Worley,
+    feature: WorleyFeature,
+    options: WorleyOptions,
+}
+
+impl WorleyTex {
+    pub fn new(feature: WorleyFeature, options: WorleyOptions) -> Self {
+        Self {
+            worley: Worley::new(options.seed, options.jitter),
+            feature,
+            options,
+        }
+    }
+
+    fn cell_color(&self, id: u64) -> Color {
+        if self.options.palette.is_empty() {
+            let channel = |shift: u32| ((id >> shift) & 0xff) as f64 / 255.0;
+            return Color {
+                x: channel(0),
+                y: channel(8),
+                z: channel(16),
+            };
+        }
+        self.options.palette[(id % self.options.palette.len() as u64) as usize]
+    }
+}
+
+impl Texture for WorleyTex {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let scale = self.options.scale;
+        let cells = match self.options.space {
+            NoiseSpace::Point => self.worley.cells(&(*p * scale), self.options.metric),
+            NoiseSpace::Uv => self
+                .worley
+                .cells_2d(uv.0 * scale, uv.1 * scale, self.options.metric),
+        };
+        match self.feature {
+            WorleyFeature::F1 => Color::splat(cells.f1.min(1.0)),
+            WorleyFeature::F2 => Color::splat(cells.f2.min(1.0)),
+            WorleyFeature::F2MinusF1 => Color::splat((cells.f2 - cells.f1).min(1.0)),
+            WorleyFeature::CellColor => self.cell_color(cells.id),
+        }
     }
 }
@@ -0,0 +1,358 @@
+use crate::{
+    interval::Interval,
+    texture::Texture,
+    vec::{Color, Point},
+};
+
+/// Blends two textures by a mask: where the scalar of the mask is 0 the value is `a`, where it's 1
+/// it is `b`.
+#[derive(Debug, Clone)]
+pub struct MixTex<A: Texture, B: Texture, M: Texture> {
+    a: A,
+    b: B,
+    mask: M,
+}
+
+impl<A: Texture, B: Texture, M: Texture> MixTex<A, B, M> {
+    pub fn new(a: A, b: B, mask: M) -> Self {
+        Self { a, b, mask }
+    }
+}
+
+impl<A: Texture, B: Texture, M: Texture> Texture for MixTex<A, B, M> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        let t = self.mask.scalar(uv, p).clamp(0.0, 1.0);
+        self.a.filtered(uv, p, width) * (1.0 - t) + self.b.filtered(uv, p, width) * t
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        let t = self.mask.scalar(uv, p).clamp(0.0, 1.0);
+        self.a.alpha(uv, p) * (1.0 - t) + self.b.alpha(uv, p) * t
+    }
+}
+
+/// Product of two textures per channel, e.g. to darken a colour by an ambient occlusion or dirt
+/// map.
+#[derive(Debug, Clone)]
+pub struct MultiplyTex<A: Texture, B: Texture> {
+    a: A,
+    b: B,
+}
+
+impl<A: Texture, B: Texture> MultiplyTex<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for MultiplyTex<A, B> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        &self.a.filtered(uv, p, width) * &self.b.filtered(uv, p, width)
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.a.alpha(uv, p) * self.b.alpha(uv, p)
+    }
+}
+
+/// Sum of two textures per channel. The result isn't clamped, so it can be used for emission.
+#[derive(Debug, Clone)]
+pub struct AddTex<A: Texture, B: Texture> {
+    a: A,
+    b: B,
+}
+
+impl<A: Texture, B: Texture> AddTex<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for AddTex<A, B> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        self.a.filtered(uv, p, width) + self.b.filtered(uv, p, width)
+    }
+}
+
+/// One minus the texture per channel, e.g. to turn a glossiness into a roughness map.
+#[derive(Debug, Clone)]
+pub struct InvertTex<T: Texture> {
+    tex: T,
+}
+
+impl<T: Texture> InvertTex<T> {
+    pub fn new(tex: T) -> Self {
+        Self { tex }
+    }
+}
+
+impl<T: Texture> Texture for InvertTex<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        Color::white() - self.tex.filtered(uv, p, width)
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.tex.alpha(uv, p)
+    }
+}
+
+/// Linearly maps every channel of a texture from the range `from` to the range `to` and clamps the
+/// result to `to`.
+#[derive(Debug, Clone)]
+pub struct RemapTex<T: Texture> {
+    tex: T,
+    from: Interval,
+    to: Interval,
+}
+
+impl<T: Texture> RemapTex<T> {
+    pub fn new(tex: T, from: Interval, to: Interval) -> Self {
+        Self { tex, from, to }
+    }
+
+    // clamps the channels of the texture to `range` without remapping them
+    pub fn clamp(tex: T, range: Interval) -> Self {
+        Self::new(tex, range, range)
+    }
+
+    fn remap(&self, c: f64) -> f64 {
+        let size = self.from.size();
+        let t = if size != 0.0 {
+            (c - self.from.min) / size
+        } else {
+            0.0
+        };
+        self.to.clamp(self.to.min + t * self.to.size())
+    }
+}
+
+impl<T: Texture> Texture for RemapTex<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        let c = self.tex.filtered(uv, p, width);
+        Color {
+            x: self.remap(c.x),
+            y: self.remap(c.y),
+            z: self.remap(c.z),
+        }
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.tex.alpha(uv, p)
+    }
+}
+
+/// Colour ramp: looks up the scalar of a texture in a gradient given by colour stops and
+/// interpolates linearly between them. Scalars outside of the stops get the colour of the first or
+/// last one.
+#[derive(Debug, Clone)]
+pub struct RampTex<T: Texture> {
+    tex: T,
+    // (position, colour), sorted by position
+    stops: Vec<(f64, Color)>,
+}
+
+impl<T: Texture> RampTex<T> {
+    pub fn new(tex: T, mut stops: Vec<(f64, Color)>) -> Self {
+        assert!(!stops.is_empty(), "a colour ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { tex, stops }
+    }
+}
+
+impl<T: Texture> Texture for RampTex<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        let t = self.tex.scalar(uv, p);
+        let idx = self.stops.partition_point(|stop| stop.0 <= t);
+        if idx == 0 {
+            return self.stops[0].1;
+        }
+        if idx == self.stops.len() {
+            return self.stops[idx - 1].1;
+        }
+        let (t0, c0) = self.stops[idx - 1];
+        let (t1, c1) = self.stops[idx];
+        let f = (t - t0) / (t1 - t0);
+        c0 * (1.0 - f) + c1 * f
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.tex.alpha(uv, p)
+    }
+}
+
+/// Adjusts the colour of a texture in HSV: rotates the hue by `hue_shift` degrees and scales the
+/// saturation and the value.
+#[derive(Debug, Clone)]
+pub struct HsvTex<T: Texture> {
+    tex: T,
+    hue_shift: f64,
+    saturation: f64,
+    value: f64,
+}
+
+impl<T: Texture> HsvTex<T> {
+    pub fn new(tex: T, hue_shift: f64, saturation: f64, value: f64) -> Self {
+        Self {
+            tex,
+            hue_shift,
+            saturation,
+            value,
+        }
+    }
+}
+
+impl<T: Texture> Texture for HsvTex<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered(uv, p, 0.0)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        let (h, s, v) = rgb_to_hsv(&self.tex.filtered(uv, p, width));
+        hsv_to_rgb(
+            (h + self.hue_shift).rem_euclid(360.0),
+            (s * self.saturation).clamp(0.0, 1.0),
+            (v * self.value).max(0.0),
+        )
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.tex.alpha(uv, p)
+    }
+}
+
+// hue in degrees, saturation and value in [0,1] for colours in [0,1]
+fn rgb_to_hsv(c: &Color) -> (f64, f64, f64) {
+    let max = c.x.max(c.y).max(c.z);
+    let min = c.x.min(c.y).min(c.z);
+    let delta = max - min;
+
+    let hue = if delta <= 0.0 {
+        0.0
+    } else if max == c.x {
+        60.0 * ((c.y - c.z) / delta).rem_euclid(6.0)
+    } else if max == c.y {
+        60.0 * ((c.z - c.x) / delta + 2.0)
+    } else {
+        60.0 * ((c.x - c.y) / delta + 4.0)
+    };
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    (hue, saturation, max)
+}
+
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> Color {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    Color {
+        x: r + m,
+        y: g + m,
+        z: b + m,
+    }
+}
+
+/// Transformation of the uv coordinates a texture is looked up with. The uvs are scaled, then
+/// rotated counter-clockwise by `rotation` degrees around (0.5, 0.5) and then offset.
+#[derive(Debug, Clone, Copy)]
+pub struct UvTransform {
+    pub scale: (f64, f64),
+    pub rotation: f64,
+    pub offset: (f64, f64),
+    // wrap the transformed uvs back into [0,1), which repeats the texture `scale` times
+    pub tile: bool,
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self {
+            scale: (1.0, 1.0),
+            rotation: 0.0,
+            offset: (0.0, 0.0),
+            tile: true,
+        }
+    }
+}
+
+impl UvTransform {
+    fn apply(&self, uv: (f64, f64)) -> (f64, f64) {
+        let (u, v) = (uv.0 * self.scale.0 - 0.5, uv.1 * self.scale.1 - 0.5);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (u, v) = (
+            u * cos - v * sin + 0.5 + self.offset.0,
+            u * sin + v * cos + 0.5 + self.offset.1,
+        );
+        if self.tile {
+            (u.rem_euclid(1.0), v.rem_euclid(1.0))
+        } else {
+            (u, v)
+        }
+    }
+}
+
+/// Looks up a texture with transformed uv coordinates, e.g. to repeat a tile texture or to turn
+/// stripes diagonal.
+#[derive(Debug, Clone)]
+pub struct UvTransformTex<T: Texture> {
+    tex: T,
+    transform: UvTransform,
+}
+
+impl<T: Texture> UvTransformTex<T> {
+    pub fn new(tex: T, transform: UvTransform) -> Self {
+        Self { tex, transform }
+    }
+}
+
+impl<T: Texture> Texture for UvTransformTex<T> {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.tex.value(self.transform.apply(uv), p)
+    }
+
+    fn filtered(&self, uv: (f64, f64), p: &Point, width: f64) -> Color {
+        // scaling the uvs up shrinks the texture, so the footprint covers more of it
+        let scale = self
+            .transform
+            .scale
+            .0
+            .abs()
+            .max(self.transform.scale.1.abs());
+        self.tex
+            .filtered(self.transform.apply(uv), p, width * scale)
+    }
+
+    fn spectral_emission(&self, uv: (f64, f64), p: &Point, lambda: f64) -> f64 {
+        self.tex
+            .spectral_emission(self.transform.apply(uv), p, lambda)
+    }
+
+    fn alpha(&self, uv: (f64, f64), p: &Point) -> f64 {
+        self.tex.alpha(self.transform.apply(uv), p)
+    }
+}
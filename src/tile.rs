@@ -0,0 +1,337 @@
+use std::path::{Path, PathBuf};
+
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    imageops, ImageError, ImageResult, Rgb, RgbImage,
+};
+
+use crate::{
+    utils::{linear_to_gamma, map_rgb},
+    vec::Pixel,
+};
+
+/// Rectangular sub-area of the full image in pixel coordinates. `x` and `y` are the top-left
+/// corner of the region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn full(img_w: u32, img_h: u32) -> Self {
+        Self::new(0, 0, img_w, img_h)
+    }
+
+    /// Splits an image into a `cols` x `rows` grid of regions (row-major order). Tiles in the last
+    /// column/row absorb the remainder so the grid always covers the whole image.
+    pub fn grid(img_w: u32, img_h: u32, cols: u32, rows: u32) -> Vec<Self> {
+        let cols = cols.clamp(1, img_w.max(1));
+        let rows = rows.clamp(1, img_h.max(1));
+        let tile_w = img_w / cols;
+        let tile_h = img_h / rows;
+
+        let mut regions = Vec::with_capacity((cols * rows) as usize);
+        for j in 0..rows {
+            for i in 0..cols {
+                let x = i * tile_w;
+                let y = j * tile_h;
+                let w = if i == cols - 1 { img_w - x } else { tile_w };
+                let h = if j == rows - 1 { img_h - y } else { tile_h };
+                regions.push(Self::new(x, y, w, h));
+            }
+        }
+        regions
+    }
+
+    /// Clips the region so that it lies inside an image of the given size.
+    pub fn clip(&self, img_w: u32, img_h: u32) -> Self {
+        let x = self.x.min(img_w);
+        let y = self.y.min(img_h);
+        Self {
+            x,
+            y,
+            w: self.w.min(img_w - x),
+            h: self.h.min(img_h - y),
+        }
+    }
+
+    // whether the region lies completely inside of `other`
+    pub fn fits(&self, other: &Region) -> bool {
+        self.x >= other.x
+            && self.y >= other.y
+            && self.x as u64 + self.w as u64 <= other.x as u64 + other.w as u64
+            && self.y as u64 + self.h as u64 <= other.y as u64 + other.h as u64
+    }
+
+    pub fn area(&self) -> usize {
+        self.w as usize * self.h as usize
+    }
+
+    /// Builds the file path for a tile of the `img_w` x `img_h` image at `path`. The tile's
+    /// offset and the size of the full image are encoded in the file name, e.g. `poster.png` ->
+    /// `poster@400_300_1600x900.png`, so that `merge` can place it and check that no tile is
+    /// missing.
+    pub fn tile_path(&self, path: impl AsRef<Path>, img_w: u32, img_h: u32) -> PathBuf {
+        let path = path.as_ref();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let tag = format!("{}_{}_{img_w}x{img_h}", self.x, self.y);
+        let name = match path.extension() {
+            Some(ext) => format!("{stem}@{tag}.{}", ext.to_string_lossy()),
+            None => format!("{stem}@{tag}"),
+        };
+        path.with_file_name(name)
+    }
+
+    /// Inverse of `tile_path`: recovers the tile offset and the size of the full image from its
+    /// file name. Width and height of the tile are left at zero since they're only known once the
+    /// image is loaded.
+    pub fn from_tile_path(path: impl AsRef<Path>) -> Option<(Self, (u32, u32))> {
+        let stem = path.as_ref().file_stem()?.to_str()?;
+        let (_, tag) = stem.rsplit_once('@')?;
+        let (offset, size) = tag.rsplit_once('_')?;
+        let (x, y) = offset.split_once('_')?;
+        let (img_w, img_h) = size.split_once('x')?;
+        Some((
+            Self::new(x.parse().ok()?, y.parse().ok()?, 0, 0),
+            (img_w.parse().ok()?, img_h.parse().ok()?),
+        ))
+    }
+}
+
+/// Rendered pixels of a `Region`. Pixels are stored in linear space (averaged over `samples`
+/// rays) so that tiles can still be accumulated before being tone mapped into an image.
+#[derive(Clone, Debug)]
+pub struct Tile {
+    pub region: Region,
+    pub samples: u32,
+    pub pixels: Vec<Pixel>,
+}
+
+impl Tile {
+    pub fn new(region: Region, samples: u32) -> Self {
+        Self {
+            region,
+            samples,
+            pixels: vec![Pixel::zero(); region.area()],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Pixel {
+        self.pixels[y as usize * self.region.w as usize + x as usize]
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        let mut image = RgbImage::new(self.region.w, self.region.h);
+        for (px, out) in self.pixels.iter().zip(image.pixels_mut()) {
+            *out = Rgb::from([
+                map_rgb(linear_to_gamma(px.x)) as u8,
+                map_rgb(linear_to_gamma(px.y)) as u8,
+                map_rgb(linear_to_gamma(px.z)) as u8,
+            ]);
+        }
+        image
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        self.to_image().save(path)
+    }
+
+    /// Saves the tile of an `img_w` x `img_h` image next to `path` using `Region::tile_path` so
+    /// that it can be merged later.
+    pub fn save_as_tile(
+        &self,
+        path: impl AsRef<Path>,
+        img_w: u32,
+        img_h: u32,
+    ) -> ImageResult<PathBuf> {
+        let tile_path = self.region.tile_path(path, img_w, img_h);
+        self.save(&tile_path)?;
+        Ok(tile_path)
+    }
+
+    /// Pastes the tile into a full-size image at the tile's region offset.
+    pub fn composite_onto(&self, image: &mut RgbImage) {
+        imageops::replace(
+            image,
+            &self.to_image(),
+            self.region.x as i64,
+            self.region.y as i64,
+        );
+    }
+}
+
+fn tile_error(msg: String) -> ImageError {
+    ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(msg)))
+}
+
+// region of a tile file in the full image and the size of the full image
+fn load_tile(path: &Path) -> ImageResult<(Region, (u32, u32), RgbImage)> {
+    let (region, frame) = Region::from_tile_path(path).ok_or_else(|| {
+        tile_error(format!(
+            "no tile offset and image size in file name: {}",
+            path.display()
+        ))
+    })?;
+    let image = image::open(path)?.into_rgb8();
+    let region = Region {
+        w: image.width(),
+        h: image.height(),
+        ..region
+    };
+    if !region.fits(&Region::full(frame.0, frame.1)) {
+        return Err(tile_error(format!(
+            "tile {} {region:?} is outside of the {}x{} image",
+            path.display(),
+            frame.0,
+            frame.1
+        )));
+    }
+    Ok((region, frame, image))
+}
+
+/// Stitches tile files written by `Tile::save_as_tile` into a single image. All tiles have to
+/// belong to the same image and cover it completely.
+pub fn merge(tiles: &[impl AsRef<Path>], out: impl AsRef<Path>) -> ImageResult<()> {
+    let tiles = tiles
+        .iter()
+        .map(|path| load_tile(path.as_ref()))
+        .collect::<ImageResult<Vec<_>>>()?;
+
+    let Some(&(_, (img_w, img_h), _)) = tiles.first() else {
+        return Err(tile_error("no tiles to merge".to_string()));
+    };
+    if let Some((_, (w, h), _)) = tiles.iter().find(|(_, frame, _)| *frame != (img_w, img_h)) {
+        return Err(tile_error(format!(
+            "tiles of a {w}x{h} and a {img_w}x{img_h} image can't be merged"
+        )));
+    }
+
+    let mut image = RgbImage::new(img_w, img_h);
+    let mut covered = vec![false; Region::full(img_w, img_h).area()];
+    for (region, _, tile) in tiles.iter() {
+        imageops::replace(&mut image, tile, region.x as i64, region.y as i64);
+        for y in region.y..region.y + region.h {
+            let row = y as usize * img_w as usize;
+            covered[row + region.x as usize..row + (region.x + region.w) as usize].fill(true);
+        }
+    }
+    let missing = covered.iter().filter(|covered| !**covered).count();
+    if missing > 0 {
+        return Err(tile_error(format!(
+            "tiles are missing: {missing} pixels of the {img_w}x{img_h} image aren't covered"
+        )));
+    }
+    image.save(out)
+}
+
+/// Pastes tile files on top of an existing full-size render, e.g. to patch a noisy area with a
+/// crop that was re-rendered at a higher sample count.
+pub fn composite(
+    base: impl AsRef<Path>,
+    tiles: &[impl AsRef<Path>],
+    out: impl AsRef<Path>,
+) -> ImageResult<()> {
+    let mut image = image::open(base)?.into_rgb8();
+    for path in tiles.iter() {
+        let (region, frame, tile) = load_tile(path.as_ref())?;
+        if frame != image.dimensions() {
+            return Err(tile_error(format!(
+                "tile {} belongs to a {}x{} image, not to a {}x{} one",
+                path.as_ref().display(),
+                frame.0,
+                frame.1,
+                image.width(),
+                image.height()
+            )));
+        }
+        imageops::replace(&mut image, &tile, region.x as i64, region.y as i64);
+    }
+    image.save(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // fresh directory for the tile files of a single test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rstrace-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn save_tile(dir: &Path, region: Region, img_w: u32, img_h: u32) -> PathBuf {
+        Tile::new(region, 1)
+            .save_as_tile(dir.join("frame.png"), img_w, img_h)
+            .unwrap()
+    }
+
+    #[test]
+    fn tile_path_round_trip() {
+        let region = Region::new(400, 300, 200, 100);
+        let path = region.tile_path("renders/poster.png", 1600, 900);
+        assert_eq!(path, Path::new("renders/poster@400_300_1600x900.png"));
+        assert_eq!(
+            Region::from_tile_path(&path),
+            Some((Region::new(400, 300, 0, 0), (1600, 900)))
+        );
+
+        let path = region.tile_path("a@b", 1600, 900);
+        assert_eq!(path, Path::new("a@b@400_300_1600x900"));
+        assert_eq!(
+            Region::from_tile_path(&path),
+            Some((Region::new(400, 300, 0, 0), (1600, 900)))
+        );
+    }
+
+    #[test]
+    fn malformed_tile_paths() {
+        for path in [
+            "poster.png",
+            "poster@.png",
+            "poster@400_300.png",
+            "poster@400_300_1600.png",
+            "poster@400_300_1600x.png",
+            "poster@400_-300_1600x900.png",
+            "poster@a_300_1600x900.png",
+            "poster@400_300_1600x900x2.png",
+        ] {
+            assert_eq!(Region::from_tile_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn merge_rejects_tiles_of_different_images() {
+        let dir = temp_dir("merge-frames");
+        let tiles = [
+            save_tile(&dir, Region::new(0, 0, 2, 4), 4, 4),
+            save_tile(&dir, Region::new(2, 0, 2, 4), 5, 4),
+        ];
+        let err = merge(&tiles, dir.join("out.png")).unwrap_err();
+        assert!(err.to_string().contains("can't be merged"), "{err}");
+        assert!(!dir.join("out.png").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn merge_rejects_incomplete_coverage() {
+        let dir = temp_dir("merge-coverage");
+        let left = save_tile(&dir, Region::new(0, 0, 2, 4), 4, 4);
+        let err = merge(&[&left], dir.join("out.png")).unwrap_err();
+        assert!(err.to_string().contains("8 pixels"), "{err}");
+        assert!(!dir.join("out.png").exists());
+
+        let right = save_tile(&dir, Region::new(2, 0, 2, 4), 4, 4);
+        merge(&[&left, &right], dir.join("out.png")).unwrap();
+        assert_eq!(image::open(dir.join("out.png")).unwrap().width(), 4);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -13,6 +13,24 @@ pub fn linear_to_gamma(val: f64) -> f64 {
     val.sqrt()
 }
 
+// decodes an sRGB encoded value in [0,1] into linear
+pub fn srgb_to_linear(val: f64) -> f64 {
+    if val <= 0.04045 {
+        val / 12.92
+    } else {
+        ((val + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// encodes a linear value in [0,1] with the sRGB transfer function
+pub fn linear_to_srgb(val: f64) -> f64 {
+    if val <= 0.0031308 {
+        val * 12.92
+    } else {
+        1.055 * val.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 const RGB_INTERVAL: Interval = Interval {
     min: 0.0,
     max: 255.99,
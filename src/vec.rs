@@ -1,4 +1,4 @@
-use image::Rgb;
+use image::{Rgb, Rgba};
 use rand::{Rng, RngCore};
 
 /// Three-dimensional vector that's used for points, colors, offsets etc.
@@ -165,6 +165,69 @@ impl Vec3 {
     }
 }
 
+/// Orthonormal basis around the `w` axis, used to move directions between world space and the
+/// local shading space of a surface (where the normal is the z-axis).
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn from_w(w: &Vec3) -> Self {
+        let w = w.norm();
+        // pick the world axis that's least aligned with w so the cross product doesn't degenerate
+        let a = if w.x.abs() > 0.9 {
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            }
+        } else {
+            Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+        };
+        let v = w.cross(&a).norm();
+        let u = w.cross(&v);
+        Self { u, v, w }
+    }
+
+    // frame around the normal w whose u axis follows the tangent of the surface, projected into
+    // the plane of w, so that anisotropic materials line up with the uv parameterisation. Falls
+    // back to the bitangent and then to an arbitrary frame when they are degenerate.
+    pub fn from_w_tangents(w: &Vec3, tangent: &Vec3, bitangent: &Vec3) -> Self {
+        let w = w.norm();
+        let project = |t: &Vec3| *t - w * w.dot(t);
+        let (t, b) = (project(tangent), project(bitangent));
+        let (u, v) = if !t.near_zero() {
+            let u = t.norm();
+            (u, w.cross(&u))
+        } else if !b.near_zero() {
+            let v = b.norm();
+            (v.cross(&w), v)
+        } else {
+            return Self::from_w(&w);
+        };
+        Self { u, v, w }
+    }
+
+    pub fn to_world(&self, local: &Vec3) -> Vec3 {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    pub fn to_local(&self, world: &Vec3) -> Vec3 {
+        Vec3 {
+            x: world.dot(&self.u),
+            y: world.dot(&self.v),
+            z: world.dot(&self.w),
+        }
+    }
+}
+
 impl Color {
     pub fn red() -> Self {
         Self {
@@ -210,6 +273,7 @@ impl From<(u8, u8, u8)> for Color {
     }
 }
 
+// the raw values scaled to [0,1], sRGB encoded pixels still need to be decoded
 impl From<&Rgb<u8>> for Color {
     fn from(value: &Rgb<u8>) -> Self {
         let value = value.0;
@@ -217,6 +281,14 @@ impl From<&Rgb<u8>> for Color {
     }
 }
 
+// drops the alpha channel
+impl From<&Rgba<u8>> for Color {
+    fn from(value: &Rgba<u8>) -> Self {
+        let value = value.0;
+        (value[0], value[1], value[2]).into()
+    }
+}
+
 impl std::ops::Add for Vec3 {
     type Output = Self;
 
@@ -0,0 +1,382 @@
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read},
+    path::Path,
+};
+
+use crate::{aabb::AABB, texture::Texture, vec::Point};
+
+/// Scalar density of a participating medium at points in space.
+pub trait DensityField: Debug + Send + Sync {
+    fn density(&self, p: &Point) -> f64;
+    // upper bound of the density anywhere in the field. Free flights are sampled against it, so a
+    // tight bound makes tracking faster and a too small one makes it wrong.
+    fn max_density(&self) -> f64;
+}
+
+/// Density read from a (solid) texture. The texture is evaluated at the point with uv = (0,0) and
+/// its scalar value is expected to be in [0,1], which is then scaled by `scale`.
+#[derive(Debug, Clone)]
+pub struct TextureDensity<T: Texture> {
+    tex: T,
+    scale: f64,
+}
+
+impl<T: Texture> TextureDensity<T> {
+    pub fn new(tex: T, scale: f64) -> Self {
+        Self { tex, scale }
+    }
+}
+
+impl<T: Texture> DensityField for TextureDensity<T> {
+    fn density(&self, p: &Point) -> f64 {
+        self.tex.scalar((0.0, 0.0), p).clamp(0.0, 1.0) * self.scale
+    }
+
+    fn max_density(&self) -> f64 {
+        self.scale
+    }
+}
+
+// side length of the cubic bricks a sparse grid is split into
+const BRICK_SIZE: usize = 8;
+// "VOL", version, encoding, resolution, channel count and bounding box
+const VOL_HEADER_LEN: u64 = 48;
+
+#[derive(Clone)]
+enum Storage {
+    Dense(Vec<f32>),
+    // bricks of BRICK_SIZE³ voxels, x fastest. Bricks without any density aren't stored.
+    Sparse {
+        bricks: Vec<Option<Box<[f32]>>>,
+        brick_res: [usize; 3],
+    },
+}
+
+/// Density defined by a regular grid of voxels stretched over a bounding box, e.g. the output of a
+/// smoke or cloud simulation. Densities between voxels are interpolated trilinearly and the density
+/// outside of the box is 0.
+///
+/// Voxel values sit on the grid vertices, so the first and last voxel of each axis lie on the faces
+/// of the box (the convention of Mitsuba's grid volumes).
+#[derive(Clone)]
+pub struct VoxelGrid {
+    res: [usize; 3],
+    bbox: AABB,
+    storage: Storage,
+    max: f64,
+}
+
+impl Debug for VoxelGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let storage = match &self.storage {
+            Storage::Dense(_) => "dense".to_string(),
+            Storage::Sparse { bricks, .. } => format!(
+                "sparse ({}/{} bricks)",
+                bricks.iter().filter(|b| b.is_some()).count(),
+                bricks.len()
+            ),
+        };
+        f.debug_struct("VoxelGrid")
+            .field("res", &self.res)
+            .field("bbox", &self.bbox)
+            .field("storage", &storage)
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+impl VoxelGrid {
+    /// Dense grid of `res[0] * res[1] * res[2]` voxels in x-fastest order (index `(z * res[1] + y)
+    /// * res[0] + x`) that fills `bbox`.
+    pub fn new(res: [usize; 3], data: Vec<f32>, bbox: AABB) -> Self {
+        assert!(
+            res.iter().all(|&r| r > 0),
+            "voxel grid resolution must be positive: {res:?}"
+        );
+        assert_eq!(
+            data.len(),
+            res[0] * res[1] * res[2],
+            "voxel count doesn't match the grid resolution {res:?}"
+        );
+        // a single infinite voxel would make the majorant of media infinite
+        assert!(
+            data.iter().all(|d| d.is_finite()),
+            "voxel values must be finite"
+        );
+        let max = data.iter().fold(0.0_f32, |m, &d| m.max(d)) as f64;
+        Self {
+            res,
+            bbox,
+            storage: Storage::Dense(data),
+            max,
+        }
+    }
+
+    /// Converts the grid into bricks and drops the empty ones. Saves a lot of memory for smoke and
+    /// clouds, which usually only fill a small part of their box.
+    pub fn into_sparse(self) -> Self {
+        let Storage::Dense(data) = &self.storage else {
+            return self;
+        };
+        let brick_res = self.res.map(|r| r.div_ceil(BRICK_SIZE));
+        let mut bricks = Vec::with_capacity(brick_res[0] * brick_res[1] * brick_res[2]);
+        for bz in 0..brick_res[2] {
+            for by in 0..brick_res[1] {
+                for bx in 0..brick_res[0] {
+                    let mut brick = vec![0.0; BRICK_SIZE * BRICK_SIZE * BRICK_SIZE];
+                    let mut empty = true;
+                    for (i, voxel) in brick.iter_mut().enumerate() {
+                        let x = bx * BRICK_SIZE + i % BRICK_SIZE;
+                        let y = by * BRICK_SIZE + (i / BRICK_SIZE) % BRICK_SIZE;
+                        let z = bz * BRICK_SIZE + i / (BRICK_SIZE * BRICK_SIZE);
+                        if x < self.res[0] && y < self.res[1] && z < self.res[2] {
+                            *voxel = data[(z * self.res[1] + y) * self.res[0] + x];
+                            empty &= *voxel == 0.0;
+                        }
+                    }
+                    bricks.push((!empty).then(|| brick.into_boxed_slice()));
+                }
+            }
+        }
+        Self {
+            storage: Storage::Sparse { bricks, brick_res },
+            ..self
+        }
+    }
+
+    /// Moves and stretches the grid to fill `bbox`.
+    pub fn with_bbox(mut self, bbox: AABB) -> Self {
+        self.bbox = bbox;
+        self
+    }
+
+    pub fn bbox(&self) -> AABB {
+        self.bbox
+    }
+
+    pub fn res(&self) -> [usize; 3] {
+        self.res
+    }
+
+    /// Loads a Mitsuba grid volume (`.vol`, version 3). Grids with float32, float16 and uint8
+    /// (mapped to [0,1]) voxels are supported. Multi channel grids are averaged into one density.
+    /// The grid fills the bounding box stored in the file, use `with_bbox` to place it elsewhere.
+    pub fn from_vol<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut r = BufReader::new(file);
+
+        let mut header = [0u8; 4];
+        r.read_exact(&mut header)?;
+        if &header[..3] != b"VOL" {
+            return Err(invalid_data(
+                "not a grid volume, missing VOL header".to_string(),
+            ));
+        }
+        if header[3] != 3 {
+            return Err(invalid_data(format!(
+                "unsupported grid volume version {}",
+                header[3]
+            )));
+        }
+
+        let encoding = read_i32(&mut r)?;
+        let mut res = [0; 3];
+        for r_axis in res.iter_mut() {
+            *r_axis = usize::try_from(read_i32(&mut r)?)
+                .ok()
+                .filter(|&v| v > 0)
+                .ok_or_else(|| invalid_data("invalid grid resolution".to_string()))?;
+        }
+        let channels = usize::try_from(read_i32(&mut r)?)
+            .ok()
+            .filter(|&c| c > 0)
+            .ok_or_else(|| invalid_data("invalid channel count".to_string()))?;
+        let mut bounds = [0.0; 6];
+        for b in bounds.iter_mut() {
+            *b = read_f32(&mut r)? as f64;
+        }
+        let bbox = AABB::from_points(
+            &Point {
+                x: bounds[0],
+                y: bounds[1],
+                z: bounds[2],
+            },
+            &Point {
+                x: bounds[3],
+                y: bounds[4],
+                z: bounds[5],
+            },
+        );
+
+        let value_size = match encoding {
+            1 => 4,
+            2 => 2,
+            3 => 1,
+            _ => {
+                return Err(invalid_data(format!(
+                    "unsupported grid volume encoding {encoding}"
+                )))
+            }
+        };
+        // the header is untrusted, so the size of the voxel data is checked against the file
+        // before anything is allocated
+        let len = voxel_count(res)?
+            .checked_mul(channels)
+            .and_then(|values| values.checked_mul(value_size))
+            .ok_or_else(|| invalid_data("grid volume is too large".to_string()))?;
+        if len as u64 > file_len.saturating_sub(VOL_HEADER_LEN) {
+            return Err(invalid_data(format!(
+                "grid volume is truncated, expected {len} bytes of voxels for a {res:?} grid"
+            )));
+        }
+
+        let bytes = read_bytes(&mut r, len)?;
+        let raw: Vec<f32> = match value_size {
+            4 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            2 => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            _ => bytes.iter().map(|&b| b as f32 / 255.0).collect(),
+        };
+
+        let data = raw
+            .chunks_exact(channels)
+            .map(|c| c.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Ok(Self::new(res, finite_voxels(data)?, bbox))
+    }
+
+    /// Loads a headerless grid of little endian float32 voxels in x-fastest order, as written by
+    /// most simulation tools when exporting raw data.
+    pub fn from_raw<P: AsRef<Path>>(path: P, res: [usize; 3], bbox: AABB) -> io::Result<Self> {
+        let len = voxel_count(res)?
+            .checked_mul(4)
+            .ok_or_else(|| invalid_data(format!("a {res:?} grid is too large")))?;
+        let bytes = std::fs::read(path)?;
+        if len == 0 || bytes.len() != len {
+            return Err(invalid_data(format!(
+                "raw grid has {} bytes, expected {len} for a {res:?} grid",
+                bytes.len(),
+            )));
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok(Self::new(res, finite_voxels(data)?, bbox))
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        match &self.storage {
+            Storage::Dense(data) => data[(z * self.res[1] + y) * self.res[0] + x] as f64,
+            Storage::Sparse { bricks, brick_res } => {
+                let brick = ((z / BRICK_SIZE) * brick_res[1] + y / BRICK_SIZE) * brick_res[0]
+                    + x / BRICK_SIZE;
+                match &bricks[brick] {
+                    Some(voxels) => {
+                        let (x, y, z) = (x % BRICK_SIZE, y % BRICK_SIZE, z % BRICK_SIZE);
+                        voxels[(z * BRICK_SIZE + y) * BRICK_SIZE + x] as f64
+                    }
+                    None => 0.0,
+                }
+            }
+        }
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point) -> f64 {
+        // continuous voxel coordinates and the lower voxel plus offset of the cell around them
+        let mut cell = [(0, 0.0); 3];
+        for (axis, c) in p.iter().enumerate() {
+            let interval = self.bbox.axis_interval(axis);
+            if !interval.contains(c) {
+                return 0.0;
+            }
+            let last = self.res[axis] - 1;
+            let size = interval.size();
+            let v = if size > 0.0 {
+                (c - interval.min) / size * last as f64
+            } else {
+                0.0
+            };
+            let i = (v.floor() as usize).min(last.saturating_sub(1));
+            cell[axis] = (i, (v - i as f64).clamp(0.0, 1.0));
+        }
+
+        let [(x, fx), (y, fy), (z, fz)] = cell;
+        let next = |i: usize, axis: usize| (i + 1).min(self.res[axis] - 1);
+        let (x1, y1, z1) = (next(x, 0), next(y, 1), next(z, 2));
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.voxel(x, y, z), self.voxel(x1, y, z), fx);
+        let c10 = lerp(self.voxel(x, y1, z), self.voxel(x1, y1, z), fx);
+        let c01 = lerp(self.voxel(x, y, z1), self.voxel(x1, y, z1), fx);
+        let c11 = lerp(self.voxel(x, y1, z1), self.voxel(x1, y1, z1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz).max(0.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+fn voxel_count(res: [usize; 3]) -> io::Result<usize> {
+    res[0]
+        .checked_mul(res[1])
+        .and_then(|n| n.checked_mul(res[2]))
+        .ok_or_else(|| invalid_data(format!("a {res:?} grid is too large")))
+}
+
+// files are untrusted, so infinite and NaN voxels are an error instead of a panic in `new`
+fn finite_voxels(data: Vec<f32>) -> io::Result<Vec<f32>> {
+    match data.iter().position(|d| !d.is_finite()) {
+        Some(i) => Err(invalid_data(format!(
+            "voxel {i} has the non-finite value {}",
+            data[i]
+        ))),
+        None => Ok(data),
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_bytes(r: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+// IEEE 754 half precision to single precision
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mantissa = (h & 0x3ff) as f32;
+    match exp {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use rand::rngs::SmallRng;
+use rstrace::bvh::BvhNode;
+use rstrace::camera::{Camera, CameraIntrinsics, CameraPose};
+use rstrace::distributed::{self, Coordinator, Job};
+use rstrace::geometry::Sphere;
+use rstrace::material::{Lambertian, Metal};
+use rstrace::ray::{Hittable, Hittables};
+use rstrace::texture::SolidTex;
+use rstrace::tile::Tile;
+use rstrace::vec::*;
+
+fn camera() -> Camera<SmallRng> {
+    let mut intrinsics = CameraIntrinsics::default();
+    intrinsics.img_w = 32;
+    intrinsics.max_bounces = 4;
+    Camera::new_seeded_rng(intrinsics, CameraPose::default(), 7)
+}
+
+fn world(rng: &mut SmallRng) -> Arc<dyn Hittable<SmallRng>> {
+    let mut world = Hittables::new();
+    world.add(Sphere::new_arc(
+        100.0,
+        Point {
+            x: 0.0,
+            y: -100.5,
+            z: -1.0,
+        },
+        Lambertian::new(SolidTex::new(Color::splat(0.5))),
+    ));
+    world.add(Sphere::new_arc(
+        0.5,
+        Point {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        },
+        Metal::new(SolidTex::new(Color::splat(0.8)), 0.3),
+    ));
+    BvhNode::from_hittables(&mut world.objects, rng)
+}
+
+fn render(camera: &mut Camera<SmallRng>, world: &Arc<dyn Hittable<SmallRng>>, job: &Job) -> Tile {
+    camera.set_region(Some(job.region));
+    camera.set_rays_per_pixel(job.samples);
+    camera.set_seed(job.seed);
+    camera.render_tile(world.clone())
+}
+
+#[test]
+fn workers_render_the_same_frame_as_a_single_process() {
+    let (img_w, img_h) = camera().img_dims();
+    let jobs = Job::tiles(img_w, img_h, 3, 2, 4);
+
+    let coordinator = Coordinator::bind("127.0.0.1:0", img_w, img_h, jobs.clone()).unwrap();
+    let addr = coordinator.local_addr().unwrap();
+    let (frame_tx, frame_rx) = mpsc::channel();
+    thread::spawn(move || frame_tx.send(coordinator.run()));
+
+    // a worker that dies after receiving its first job, which has to be put back into the queue
+    let stream = TcpStream::connect(addr).unwrap();
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).unwrap();
+    assert!(line.starts_with("JOB "), "unexpected message: {line:?}");
+    drop(stream);
+
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut camera = camera();
+                let world = world(&mut camera.get_rng());
+                distributed::work(addr, |job| render(&mut camera, &world, job))
+            })
+        })
+        .collect();
+
+    let frame = frame_rx
+        .recv_timeout(Duration::from_secs(300))
+        .expect("coordinator didn't finish")
+        .unwrap();
+    for worker in workers {
+        worker.join().unwrap().unwrap();
+    }
+
+    let mut camera = camera();
+    let world = world(&mut camera.get_rng());
+    assert_eq!(frame.samples, 4);
+    for job in &jobs {
+        let tile = render(&mut camera, &world, job);
+        let r = job.region;
+        for y in 0..r.h {
+            for x in 0..r.w {
+                let (expected, actual) = (tile.get(x, y), frame.get(r.x + x, r.y + y));
+                // workers send their pixels as f32
+                assert!(
+                    (expected - actual).len() <= 1e-6 * expected.len().max(1.0),
+                    "pixel ({}, {}) is {actual:?}, expected {expected:?}",
+                    r.x + x,
+                    r.y + y
+                );
+            }
+        }
+    }
+}